@@ -0,0 +1,453 @@
+use std::{collections::{HashMap, HashSet}, sync::OnceLock};
+
+use crate::{error, errors::{DynamicError, EvaluatorError, Warning}, evaluator::{environment::Environment, object::{NativeFunctionObject, Object, DISCARD, FUNCTION_PREFIX}, yaipl_std}, lexer::token::Position, parser::ast::{ArithmeticOperator, Assignment, BinaryExpression, BlockStatement, BreakStatement, CallExpression, ContinueStatement, ElseStatement, Expression, ExpressionStatement, ForStatement, FunctionCallExpression, FunctionDeclareExpression, IfStatement, IndexExpression, IntegerLiteral, Literal, MatchArm, MatchExpression, MethodCallExpression, Node, Operator, Parameter, PropertyExpression, ReturnStatement, ThrowStatement, TryStatement, UnaryExpression, WhileStatement}};
+
+#[derive(Debug, Clone, Default)]
+pub struct AnalyzerOptions {
+    /// Warn when a function parameter shadows a variable from an enclosing scope
+    pub warn_shadowing: bool,
+}
+
+/// Lightweight pass over a parsed program which collects non-fatal warnings, and catches
+/// calls to built-ins with too many arguments before anything runs
+pub struct Analyzer<'a> {
+    pub options: AnalyzerOptions,
+    ast: &'a [Node],
+    warnings: Vec<Warning>,
+    assigned: Vec<(&'a str, &'a Position)>,
+    used: HashSet<&'a str>,
+    /// Names bound in the current scope and the ones enclosing it
+    scope: Vec<(&'a str, &'a Position)>,
+    /// Prefixed names of every function the program declares, as they replace built-ins
+    declared: HashSet<&'a str>,
+    calls: Vec<(&'a str, usize, &'a Position)>,
+    /// Environment the program will run in. Its bindings decide what a call refers to, as the
+    /// host, a prelude or an earlier file may have replaced a built-in
+    globals: Option<&'a Environment>,
+}
+
+/// The most arguments each built-in takes by its prefixed name, or `None` if it takes any number.
+/// Built on first use, as creating the built-ins allocates every one of them
+fn builtin_arity() -> &'static HashMap<String, Option<usize>> {
+    static ARITY: OnceLock<HashMap<String, Option<usize>>> = OnceLock::new();
+
+    ARITY.get_or_init(|| {
+        let mut builtins = Environment::new();
+        yaipl_std::initialize(&mut builtins);
+
+        builtins.iter()
+            .filter_map(|(name, object)| match object {
+                Object::NativeFunction(function) => Some((name.to_string(), native_arity(function))),
+                _ => None
+            })
+            .collect()
+    })
+}
+
+/// The most arguments a native function takes, or `None` if it takes any number
+fn native_arity(function: &NativeFunctionObject) -> Option<usize> {
+    // A rest parameter, written `...name` like in scripts, takes any number of arguments
+    let variadic = function.1.last().is_some_and(|parameter| parameter.starts_with("..."));
+    (!variadic).then_some(function.1.len())
+}
+
+impl<'a> Analyzer<'a> {
+    pub fn from(ast: &'a [Node]) -> Self {
+        Self {
+            options: AnalyzerOptions::default(),
+            ast,
+            warnings: Vec::new(),
+            assigned: Vec::new(),
+            used: HashSet::new(),
+            scope: Vec::new(),
+            declared: HashSet::new(),
+            calls: Vec::new(),
+            globals: None,
+        }
+    }
+
+    /// Analyzes a program which will run in `env`, checking calls against what it binds
+    pub fn with_env(ast: &'a [Node], env: &'a Environment) -> Self {
+        Self {
+            globals: Some(env),
+            ..Self::from(ast)
+        }
+    }
+
+    pub fn analyze(mut self) -> Result<Vec<Warning>, DynamicError> {
+        let ast = self.ast;
+        self.visit_statements(ast);
+
+        self.check_builtin_calls()?;
+        self.check_unused_variables();
+        Ok(self.warnings)
+    }
+
+    /// Built-ins treat missing arguments as void, so only passing more than they take is an error
+    fn check_builtin_calls(&self) -> Result<(), DynamicError> {
+        for (name, found, pos) in &self.calls {
+            if self.declared.contains(name) {
+                continue;
+            }
+
+            let max = match self.globals {
+                Some(env) => match env.get(name) {
+                    Some(Object::NativeFunction(function)) => native_arity(function),
+                    _ => None
+                },
+                None => builtin_arity().get(*name).copied().flatten(),
+            };
+
+            if let Some(max) = max {
+                if *found > max {
+                    error!(EvaluatorError::ArgumentCount {
+                        name: name.trim_start_matches(FUNCTION_PREFIX).to_string(),
+                        min: 0,
+                        max: Some(max),
+                        found: *found,
+                        pos: (*pos).to_owned(),
+                    })
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn check_unused_variables(&mut self) {
+        for (name, pos) in &self.assigned {
+            if !self.used.contains(name) {
+                self.warnings.push(Warning::UnusedVariable {
+                    name: name.to_string(),
+                    pos: (*pos).to_owned(),
+                });
+            }
+        }
+    }
+
+    /// Warns about `if x += 1`, which is most likely a typo. Parentheses make the intent explicit
+    fn check_condition(&mut self, condition: &Expression) {
+        if let Expression::AssignmentExpr(Assignment(_, _, pos)) = condition {
+            self.warnings.push(Warning::AssignmentInCondition {
+                pos: pos.to_owned(),
+            });
+        }
+    }
+
+    /// Warns about `x / 0` and `x % 0`, which fail whenever they run. A float `0.0` is fine, as it gives `inf` or `nan`
+    fn check_divisor(&mut self, operator: &Operator, divisor: &Expression, pos: &Position) {
+        let mut divisor = divisor;
+        while let Expression::GroupExpr(inner) = divisor {
+            divisor = inner;
+        }
+
+        if matches!(operator, Operator::Arithmetic(ArithmeticOperator::Divide | ArithmeticOperator::Modulo))
+            && matches!(divisor, Expression::LiteralExpr(Literal::Integer(IntegerLiteral(0)))) {
+            self.warnings.push(Warning::DivisionByZero {
+                pos: pos.to_owned(),
+            });
+        }
+    }
+
+    fn visit_node(&mut self, node: &'a Node) {
+        match node {
+            Node::Program(nodes) => self.visit_statements(nodes),
+            Node::BlockStatement(block) => self.visit_block(block),
+            Node::ExpressionStatement(ExpressionStatement(expression, _)) => self.visit_expression(expression),
+            Node::ReturnStatement(ReturnStatement(Some(expression), _)) => self.visit_expression(expression),
+            Node::IfStatement(IfStatement(condition, body, otherwise, _)) => {
+                self.check_condition(condition);
+                self.visit_expression(condition);
+                self.visit_block(body);
+
+                if let Some(otherwise) = otherwise {
+                    self.visit_node(otherwise);
+                }
+            },
+            Node::ElseStatement(ElseStatement(body)) => self.visit_block(body),
+            Node::WhileStatement(WhileStatement(condition, body, _)) => {
+                self.check_condition(condition);
+                self.visit_expression(condition);
+                self.visit_block(body);
+            },
+            Node::ForStatement(ForStatement(setter, condition, assignment, body, _)) => {
+                self.visit_expression(setter);
+                self.visit_expression(condition);
+                self.visit_expression(assignment);
+                self.visit_block(body);
+            },
+            Node::ThrowStatement(ThrowStatement(expression, _)) => self.visit_expression(expression),
+            Node::TryStatement(TryStatement(body, _, handler, _)) => {
+                self.visit_block(body);
+                self.visit_block(handler);
+            },
+            _ => {}
+        }
+    }
+
+    fn visit_block(&mut self, block: &'a BlockStatement) {
+        self.visit_statements(&block.0);
+    }
+
+    /// Visits a list of statements, warning once if anything follows a `return`, `break`, `continue` or `throw`
+    fn visit_statements(&mut self, nodes: &'a [Node]) {
+        let mut terminator: Option<(&str, &Position)> = None;
+        let mut reported = false;
+
+        for node in nodes {
+            if let (Some((keyword, pos)), false) = (terminator, reported || matches!(node, Node::EmptyStatement(_))) {
+                self.warnings.push(Warning::UnreachableCode {
+                    keyword: keyword.to_string(),
+                    pos: pos.to_owned(),
+                });
+                reported = true;
+            }
+
+            terminator = match node {
+                Node::ReturnStatement(ReturnStatement(_, pos)) => Some(("return", pos)),
+                Node::BreakStatement(BreakStatement(pos)) => Some(("break", pos)),
+                Node::ContinueStatement(ContinueStatement(pos)) => Some(("continue", pos)),
+                Node::ThrowStatement(ThrowStatement(_, pos)) => Some(("throw", pos)),
+                _ => terminator
+            };
+
+            self.visit_node(node);
+        }
+    }
+
+    fn visit_expression(&mut self, expression: &'a Expression) {
+        match expression {
+            Expression::AssignmentExpr(Assignment(identifier, value, pos)) => {
+                if identifier.0 != DISCARD && !self.assigned.iter().any(|(name, _)| *name == identifier.0) {
+                    self.assigned.push((&identifier.0, pos));
+                }
+
+                // Assigning to a visible name updates it rather than creating a new binding
+                if identifier.0 != DISCARD && !self.scope.iter().any(|(name, _)| *name == identifier.0) {
+                    self.scope.push((&identifier.0, pos));
+                }
+
+                self.visit_node(value);
+            },
+            Expression::IdentifierExpr(identifier, _) => {
+                self.used.insert(&identifier.0);
+            },
+            Expression::LiteralExpr(Literal::List(list)) => list.0.iter().for_each(|element| self.visit_expression(element)),
+            Expression::LiteralExpr(_) => {},
+            Expression::BinaryExpr(BinaryExpression(left, operator, right, pos, _)) => {
+                self.check_divisor(operator, right, pos);
+                self.visit_expression(left);
+                self.visit_expression(right);
+            },
+            Expression::UnaryExpr(UnaryExpression(_, expression)) => self.visit_expression(expression),
+            Expression::GroupExpr(expression) => self.visit_expression(expression),
+            Expression::BlockExpr(block) => self.visit_block(block),
+            Expression::FunctionCallExpr(FunctionCallExpression(identifier, arguments, pos)) => {
+                // Calling a plain variable falls back to its unprefixed name
+                self.used.insert(identifier.0.trim_start_matches(FUNCTION_PREFIX));
+                self.calls.push((&identifier.0, arguments.len(), pos));
+                arguments.iter().for_each(|argument| self.visit_expression(argument));
+            },
+            Expression::CallExpr(CallExpression(callee, arguments, _)) => {
+                self.visit_expression(callee);
+                arguments.iter().for_each(|argument| self.visit_expression(argument));
+            },
+            Expression::IndexExpr(IndexExpression(target, index, _)) => {
+                self.visit_expression(target);
+                self.visit_expression(index);
+            },
+            Expression::MethodCallExpr(MethodCallExpression(receiver, _, arguments, _)) => {
+                self.visit_expression(receiver);
+                arguments.iter().for_each(|argument| self.visit_expression(argument));
+            },
+            Expression::PropertyExpr(PropertyExpression(receiver, _, _)) => self.visit_expression(receiver),
+            Expression::FunctionDeclareExpr(FunctionDeclareExpression(identifier, parameters, body)) => {
+                self.declared.insert(&identifier.0);
+                let scope_size = self.scope.len();
+
+                for Parameter(identifier, default, _, pos) in parameters {
+                    if let Some(default) = default {
+                        self.visit_expression(default);
+                    }
+
+                    if identifier.0 == DISCARD {
+                        continue;
+                    }
+
+                    if self.options.warn_shadowing {
+                        if let Some((_, outer)) = self.scope[..scope_size].iter().rev().find(|(name, _)| *name == identifier.0) {
+                            self.warnings.push(Warning::ShadowedVariable {
+                                name: identifier.0.to_owned(),
+                                pos: pos.to_owned(),
+                                outer: (*outer).to_owned(),
+                            });
+                        }
+                    }
+
+                    self.scope.push((&identifier.0, pos));
+                }
+
+                self.visit_block(body);
+                self.scope.truncate(scope_size);
+            },
+            Expression::MatchExpr(MatchExpression(scrutinee, arms, _)) => {
+                self.visit_expression(scrutinee);
+                arms.iter().for_each(|MatchArm(_, body)| self.visit_expression(body));
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{errors::DynamicError, evaluator::Evaluator, testing::parse};
+
+    use super::*;
+
+    fn analyze(input: &str) -> Result<Vec<Warning>, DynamicError> {
+        match parse(input)? {
+            Node::Program(ast) => Analyzer::from(&ast).analyze(),
+            _ => unreachable!("the parser always returns a program"),
+        }
+    }
+
+    #[test]
+    fn too_many_builtin_arguments_is_an_error() {
+        let err = analyze("typeof(1, 2)").unwrap_err();
+
+        match err.downcast_ref() {
+            Some(EvaluatorError::ArgumentCount { name, max, found, .. }) => {
+                assert_eq!((name.as_str(), *max, *found), ("typeof", Some(1), 2));
+                assert!(err.to_string().contains("at most"), "{}", err);
+            },
+            _ => panic!("expected an argument count error, got {}", err),
+        }
+    }
+
+    #[test]
+    fn builtin_calls_within_arity_are_allowed() {
+        assert!(analyze("typeof()").is_ok());
+        assert!(analyze("println(1, 2, 3, 4)").is_ok());
+    }
+
+    #[test]
+    fn declared_functions_replace_builtins() {
+        assert!(analyze("typeof = (a, b) { a; }; typeof(1, 2)").is_ok());
+    }
+
+    #[test]
+    fn builtins_rebound_in_the_environment_are_not_checked() {
+        let Node::Program(ast) = parse("typeof(1, 2)").unwrap() else {
+            unreachable!("the parser always returns a program");
+        };
+
+        let mut env = Environment::new();
+        yaipl_std::initialize(&mut env);
+        assert!(Analyzer::with_env(&ast, &env).analyze().is_err());
+
+        let mut evaluator = Evaluator::with_env(&ast, env);
+        evaluator.register("typeof", |args| Ok(Object::integer(args.len() as i32)));
+        let env = evaluator.into_env();
+        assert!(Analyzer::with_env(&ast, &env).analyze().is_ok());
+    }
+
+    #[test]
+    fn unused_variable_warns() {
+        let warnings = analyze("x = 1;\ny = 2;\nprintln(y)").unwrap();
+
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert!(matches!(&warnings[0], Warning::UnusedVariable { name, pos } if name == "x" && pos.line == 1));
+    }
+
+    fn unreachable(warnings: &[Warning]) -> Vec<(&str, usize)> {
+        warnings.iter()
+            .filter_map(|warning| match warning {
+                Warning::UnreachableCode { keyword, pos } => Some((keyword.as_str(), pos.line)),
+                _ => None
+            })
+            .collect()
+    }
+
+    #[test]
+    fn code_after_return_is_unreachable() {
+        let warnings = analyze("f = () {\n    return 1;\n    println(2);\n};\nf()").unwrap();
+        assert_eq!(unreachable(&warnings), vec![("return", 2)]);
+    }
+
+    #[test]
+    fn final_return_is_reachable() {
+        let warnings = analyze("f = (x) {\n    println(x);\n    return x;\n};\nf(1)").unwrap();
+        assert!(unreachable(&warnings).is_empty(), "{:?}", warnings);
+    }
+
+    #[test]
+    fn discard_is_never_unused() {
+        assert!(analyze("_ = 1").unwrap().is_empty());
+    }
+
+    #[test]
+    fn assignment_in_condition_warns() {
+        let warnings = analyze("x = 0;\nif x += 1 { println(x); }").unwrap();
+        assert!(warnings.iter().any(|warning| matches!(warning, Warning::AssignmentInCondition { pos } if pos.line == 2)), "{:?}", warnings);
+    }
+
+    #[test]
+    fn comparison_in_condition_does_not_warn() {
+        let warnings = analyze("x = 0;\nif x == 1 { println(x); }").unwrap();
+        assert!(warnings.is_empty(), "{:?}", warnings);
+    }
+
+    fn analyze_with(options: AnalyzerOptions, input: &str) -> Result<Vec<Warning>, DynamicError> {
+        match parse(input)? {
+            Node::Program(ast) => {
+                let mut analyzer = Analyzer::from(&ast);
+                analyzer.options = options;
+                analyzer.analyze()
+            },
+            _ => unreachable!("the parser always returns a program"),
+        }
+    }
+
+    #[test]
+    fn parameter_shadowing_a_variable_warns_when_enabled() {
+        let options = AnalyzerOptions { warn_shadowing: true };
+        let warnings = analyze_with(options, "x = 1\nf = (x) { x; }\nf(x)").unwrap();
+
+        match warnings.iter().find(|warning| matches!(warning, Warning::ShadowedVariable { .. })) {
+            Some(Warning::ShadowedVariable { name, pos, outer }) => {
+                assert_eq!((name.as_str(), pos.line, outer.line), ("x", 2, 1));
+            },
+            _ => panic!("expected a shadowed variable warning, got {:?}", warnings),
+        }
+    }
+
+    #[test]
+    fn parameter_shadowing_does_not_warn_by_default() {
+        assert!(analyze("x = 1\nf = (x) { x; }\nf(x)").unwrap().is_empty());
+    }
+
+    #[test]
+    fn arity_is_checked_in_branches_that_never_run() {
+        let err = analyze("x = 1\nif false {\n    typeof(x, 2)\n}\nx").unwrap_err();
+        assert_eq!(crate::errors::error_position(&err).map(|pos| pos.line), Some(3), "{}", err);
+    }
+
+    fn division_warnings(input: &str) -> Vec<usize> {
+        analyze(input).unwrap().iter()
+            .filter_map(|warning| match warning {
+                Warning::DivisionByZero { pos } => Some(pos.line),
+                _ => None
+            })
+            .collect()
+    }
+
+    #[test]
+    fn dividing_by_a_literal_zero_warns() {
+        assert_eq!(division_warnings("x = 1\nif false { println(x / 0); }\ny = x % (0)\ny"), vec![2, 3]);
+    }
+
+    #[test]
+    fn dividing_by_a_variable_or_float_zero_does_not_warn() {
+        assert!(division_warnings("x = 0\ny = 1 / x\nz = 1 / 0.0\ny + z").is_empty());
+    }
+}
@@ -1,6 +1,6 @@
-use std::{error::Error, fmt::{Debug, Display}};
+use std::{error::Error, fmt::{Debug, Display}, time::Duration};
 
-use crate::{evaluator::object::ObjectType, lexer::token::{Position, TokenType}};
+use crate::{evaluator::object::{Object, ObjectType}, lexer::token::{Position, TokenType}};
 
 pub type DynamicError = Box<dyn std::error::Error>;
 
@@ -11,6 +11,28 @@ macro_rules! error {
     };
 }
 
+/// Errors which can point at a location in the source
+pub trait ErrorWithPosition: Error {
+    fn position(&self) -> Option<&Position>;
+}
+
+/// Finds the source position of any of the interpreter's errors
+pub fn error_position(err: &DynamicError) -> Option<Position> {
+    if let Some(err) = err.downcast_ref::<LexerError>() {
+        return err.position().cloned();
+    }
+
+    if let Some(err) = err.downcast_ref::<ParserError>() {
+        return err.position().cloned();
+    }
+
+    if let Some(err) = err.downcast_ref::<EvaluatorError>() {
+        return err.position().cloned();
+    }
+
+    None
+}
+
 macro_rules! fmt_pos {
     ($pos:expr) => {
         format!("'&_&c{{{{path}}}}:{}:{}&-&r'", $pos.line, $pos.col)
@@ -23,10 +45,21 @@ macro_rules! fmt_token {
     };
 }
 
+/// Formats the tokens a parser error expected, e.g. `Symbol` or `one of Integer, Float`
+fn fmt_expected(expected: &[TokenType]) -> String {
+    match expected {
+        [token] => fmt_token!(token),
+        _ => format!("one of {}", expected.iter().map(|token| fmt_token!(token)).collect::<Vec<_>>().join(", ")),
+    }
+}
+
 // --- Evaluator Errors ---
 #[derive(Debug, Clone)]
 pub enum EvaluatorError {
-    ObjectNotFound { name: String },
+    ObjectNotFound {
+        name: String,
+        pos: Position
+    },
     InvalidExpression {
         expected: String
     },
@@ -34,18 +67,154 @@ pub enum EvaluatorError {
         expected: Vec<ObjectType>,
         found: ObjectType,
     },
+    NotCallable {
+        found: ObjectType,
+        pos: Position
+    },
+    ArgumentCount {
+        name: String,
+        min: usize,
+        max: Option<usize>,
+        found: usize,
+        pos: Position
+    },
+    NoMatch {
+        value: String,
+        pos: Position
+    },
+    AssertionFailed {
+        left: String,
+        right: String,
+    },
+    IndexOutOfBounds {
+        index: i32,
+        length: usize,
+        pos: Position
+    },
+    Stopped {
+        pos: Position
+    },
+    Thrown {
+        value: Object,
+        pos: Position
+    },
+    UnknownMethod {
+        found: ObjectType,
+        name: String,
+        pos: Position
+    },
+    UnknownProperty {
+        found: ObjectType,
+        name: String,
+        pos: Position
+    },
+    NativePanic {
+        name: String,
+        message: String,
+        pos: Position
+    },
+    InvalidElementType {
+        index: usize,
+        expected: ObjectType,
+        found: ObjectType,
+    },
+    ZeroStep,
+    ReturnOutsideFunction {
+        pos: Position
+    },
+    IterationLimit {
+        limit: usize,
+        pos: Position
+    },
+    Timeout {
+        timeout: Duration,
+        pos: Position
+    },
+    CallDepthLimit {
+        limit: usize,
+        pos: Position
+    },
+    /// `pos` is the operand which the operator can't be used with
+    OperandTypes {
+        operator: String,
+        left: (ObjectType, Position),
+        right: (ObjectType, Position),
+        pos: Position
+    },
 }
 
 impl Error for EvaluatorError {}
+impl ErrorWithPosition for EvaluatorError {
+    fn position(&self) -> Option<&Position> {
+        match self {
+            EvaluatorError::ObjectNotFound { pos, .. }
+            | EvaluatorError::NotCallable { pos, .. } 
+            | EvaluatorError::ArgumentCount { pos, .. }
+            | EvaluatorError::NoMatch { pos, .. }
+            | EvaluatorError::IndexOutOfBounds { pos, .. }
+            | EvaluatorError::Stopped { pos }
+            | EvaluatorError::Thrown { pos, .. }
+            | EvaluatorError::UnknownMethod { pos, .. }
+            | EvaluatorError::UnknownProperty { pos, .. }
+            | EvaluatorError::NativePanic { pos, .. }
+            | EvaluatorError::ReturnOutsideFunction { pos }
+            | EvaluatorError::IterationLimit { pos, .. }
+            | EvaluatorError::Timeout { pos, .. }
+            | EvaluatorError::CallDepthLimit { pos, .. }
+            | EvaluatorError::OperandTypes { pos, .. } => Some(pos),
+            _ => None
+        }
+    }
+}
 impl Display for EvaluatorError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            EvaluatorError::ObjectNotFound { name } => 
-                write!(f, "Object '&g&*{}&-&r' not found in current scope", name),
+            EvaluatorError::ObjectNotFound { name, pos } => 
+                write!(f, "Object '&g&*{}&-&r' not found in current scope at {}", name, fmt_pos!(pos)),
             EvaluatorError::InvalidType { expected, found } => 
                 write!(f, "Invalid type, expected {}, found {:?}", fmt_token!(expected), fmt_token!(found)),
             EvaluatorError::InvalidExpression { expected } =>
                 write!(f, "Invalid expression, expected '{:?}'", expected),
+            EvaluatorError::NotCallable { found, pos } =>
+                write!(f, "Value of type {} is not callable at {}", fmt_token!(found), fmt_pos!(pos)),
+            EvaluatorError::ArgumentCount { name, min, max: Some(max), found, pos } if min == max =>
+                write!(f, "Function '&g&*{}&-&r' expects &c{}&-&r argument(s) but &c{}&-&r were given at {}", name, min, found, fmt_pos!(pos)),
+            EvaluatorError::ArgumentCount { name, min: 0, max: Some(max), found, pos } =>
+                write!(f, "Function '&g&*{}&-&r' expects at most &c{}&-&r argument(s) but &c{}&-&r were given at {}", name, max, found, fmt_pos!(pos)),
+            EvaluatorError::ArgumentCount { name, min, max: Some(max), found, pos } =>
+                write!(f, "Function '&g&*{}&-&r' expects &c{}&-&r to &c{}&-&r arguments but &c{}&-&r were given at {}", name, min, max, found, fmt_pos!(pos)),
+            EvaluatorError::ArgumentCount { name, min, max: None, found, pos } =>
+                write!(f, "Function '&g&*{}&-&r' expects at least &c{}&-&r argument(s) but &c{}&-&r were given at {}", name, min, found, fmt_pos!(pos)),
+            EvaluatorError::NoMatch { value, pos } =>
+                write!(f, "No arm of the match at {} matches '&g&*{}&-&r'", fmt_pos!(pos), value),
+            EvaluatorError::AssertionFailed { left, right } =>
+                write!(f, "Assertion failed, '&g&*{}&-&r' is not equal to '&g&*{}&-&r'", left, right),
+            EvaluatorError::IndexOutOfBounds { index, length, pos } =>
+                write!(f, "Index &c{}&-&r is out of bounds for length &c{}&-&r at {}", index, length, fmt_pos!(pos)),
+            EvaluatorError::Stopped { pos } =>
+                write!(f, "Evaluation was stopped by the host at {}", fmt_pos!(pos)),
+            EvaluatorError::Thrown { value, pos } =>
+                write!(f, "Uncaught '&g&*{}&-&r' thrown at {}", value.to_string_with_type(), fmt_pos!(pos)),
+            EvaluatorError::InvalidElementType { index, expected, found } =>
+                write!(f, "Element &c{}&-&r has type {}, expected {}", index, fmt_token!(found), fmt_token!(expected)),
+            EvaluatorError::ZeroStep =>
+                write!(f, "The step of a range can't be &c0&-&r"),
+            EvaluatorError::UnknownMethod { found, name, pos } =>
+                write!(f, "Value of type {} has no method '&g&*{}&-&r' at {}", fmt_token!(found), name, fmt_pos!(pos)),
+            EvaluatorError::UnknownProperty { found, name, pos } =>
+                write!(f, "Value of type {} has no property '&g&*{}&-&r' at {}", fmt_token!(found), name, fmt_pos!(pos)),
+            EvaluatorError::NativePanic { name, message, pos } =>
+                write!(f, "Native function '&g&*{}&-&r' panicked at {}: {}", name, fmt_pos!(pos), message),
+            EvaluatorError::ReturnOutsideFunction { pos } =>
+                write!(f, "Can't &m&*return&-&r outside of a function at {}", fmt_pos!(pos)),
+            EvaluatorError::IterationLimit { limit, pos } =>
+                write!(f, "The loop at {} went over the limit of &c{}&-&r iterations", fmt_pos!(pos), limit),
+            EvaluatorError::Timeout { timeout, pos } =>
+                write!(f, "Evaluation ran for longer than &c{:?}&-&r and was stopped at {}", timeout, fmt_pos!(pos)),
+            EvaluatorError::CallDepthLimit { limit, pos } =>
+                write!(f, "The call at {} went over the limit of &c{}&-&r nested calls", fmt_pos!(pos), limit),
+            EvaluatorError::OperandTypes { operator, left, right, .. } =>
+                write!(f, "Operator '&g&*{}&-&r' cannot be used for types {} at {} and {} at {}", operator, fmt_token!(left.0), fmt_pos!(left.1), fmt_token!(right.0), fmt_pos!(right.1)),
         }
     }
 }
@@ -65,20 +234,92 @@ pub enum ParserError {
         pos: Position
     },
 
+    RequiredAfterDefault {
+        name: String,
+        pos: Position
+    },
+
+    RestParameterNotLast {
+        name: String,
+        pos: Position
+    },
+
+    DuplicateParameter {
+        name: String,
+        pos: Position
+    },
+
+    DiscardRead {
+        pos: Position
+    },
+
+    UnclosedDelimiter {
+        delimiter: TokenType,
+        pos: Position
+    },
+
+    NestingTooDeep {
+        limit: usize,
+        pos: Position
+    },
+
+    /// The input ended where one of `expected` was required. `pos` is the last token before the end
+    UnexpectedEof {
+        expected: Vec<TokenType>,
+        pos: Position
+    },
+
     InvalidStatement,
     OutOfBounds { index: String },
 }
 
 impl Error for ParserError {}
+impl ErrorWithPosition for ParserError {
+    fn position(&self) -> Option<&Position> {
+        match self {
+            ParserError::UnexpectedToken { pos, .. } 
+            | ParserError::InvalidToken { pos, .. }
+            | ParserError::UnexpectedEof { pos, .. }
+            | ParserError::RequiredAfterDefault { pos, .. }
+            | ParserError::RestParameterNotLast { pos, .. }
+            | ParserError::DuplicateParameter { pos, .. }
+            | ParserError::DiscardRead { pos }
+            | ParserError::UnclosedDelimiter { pos, .. }
+            | ParserError::NestingTooDeep { pos, .. } => Some(pos),
+            _ => None
+        }
+    }
+}
 impl Display for ParserError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ParserError::UnexpectedToken { found, pos } => 
                 write!(f, "Unexpected token {} at {}", fmt_token!(found), fmt_pos!(pos)),
 
-            ParserError::InvalidToken { expected, found, pos } => 
-                write!(f, "Token {} was found at {}, expected {}", fmt_token!(found), fmt_pos!(pos), fmt_token!(expected)),
+            ParserError::InvalidToken { expected, found, pos } =>
+                write!(f, "Token {} was found at {}, expected {}", fmt_token!(found), fmt_pos!(pos), fmt_expected(expected)),
+
+            ParserError::UnexpectedEof { expected, pos } =>
+                write!(f, "Unexpected end of file after {}, expected {}", fmt_pos!(pos), fmt_expected(expected)),
                 
+            ParserError::RequiredAfterDefault { name, pos } =>
+                write!(f, "Required parameter '&g&*{}&-&r' follows a parameter with a default value at {}", name, fmt_pos!(pos)),
+
+            ParserError::RestParameterNotLast { name, pos } =>
+                write!(f, "Rest parameter '&g&*{}&-&r' must be the last parameter at {}", name, fmt_pos!(pos)),
+
+            ParserError::DuplicateParameter { name, pos } =>
+                write!(f, "Parameter '&g&*{}&-&r' is declared more than once at {}", name, fmt_pos!(pos)),
+
+            ParserError::DiscardRead { pos } =>
+                write!(f, "'&g&*_&-&r' can only be assigned to, but is read at {}", fmt_pos!(pos)),
+
+            ParserError::UnclosedDelimiter { delimiter, pos } =>
+                write!(f, "{} opened at {} is never closed", fmt_token!(delimiter), fmt_pos!(pos)),
+
+            ParserError::NestingTooDeep { limit, pos } =>
+                write!(f, "Expression at {} is nested more than &c{}&-&r levels deep", fmt_pos!(pos), limit),
+
             ParserError::OutOfBounds { index } => 
                 write!(f, "Out of bounds for index &c{}", index),
 
@@ -97,16 +338,138 @@ pub enum LexerError {
         character: char,
         pos: Position
     },
+    NumberOutOfRange {
+        literal: String,
+        expected: TokenType,
+        pos: Position
+    },
+    InvalidEscape {
+        escape: String,
+        pos: Position
+    },
+    InvalidNumber {
+        literal: String,
+        pos: Position
+    },
 }
 
 impl Error for LexerError {}
+impl ErrorWithPosition for LexerError {
+    fn position(&self) -> Option<&Position> {
+        match self {
+            LexerError::InvalidCharacter { pos, .. }
+            | LexerError::NumberOutOfRange { pos, .. }
+            | LexerError::InvalidEscape { pos, .. }
+            | LexerError::InvalidNumber { pos, .. } => Some(pos),
+            _ => None
+        }
+    }
+}
 impl Display for LexerError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             LexerError::OutOfBounds { index } => 
                 write!(f, "Out of bounds at index {}", index),
             LexerError::InvalidCharacter { character, pos } => 
-                write!(f, "Invalid character '{}' at {:?}", character, fmt_pos!(pos)),
+                write!(f, "Invalid character '&g&*{}&-&r' at {}", character, fmt_pos!(pos)),
+            LexerError::NumberOutOfRange { literal, expected, pos } =>
+                write!(f, "Number '&g&*{}&-&r' at {} is out of range for {}", literal, fmt_pos!(pos), fmt_token!(expected)),
+            LexerError::InvalidEscape { escape, pos } =>
+                write!(f, "Invalid escape sequence '&g&*\\{}&-&r' at {}", escape.escape_debug(), fmt_pos!(pos)),
+            LexerError::InvalidNumber { literal, pos } =>
+                write!(f, "Invalid number '&g&*{}&-&r' at {}", literal, fmt_pos!(pos)),
+        }
+    }
+}
+
+
+// --- Control flow ---
+/// Raised by the `exit` built-in to stop evaluation. It travels up through the error channel
+/// so the caller decides what to do with the code instead of the process being killed outright
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExitSignal(pub i32);
+
+impl Error for ExitSignal {}
+impl Display for ExitSignal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Exited with code {}", self.0)
+    }
+}
+
+/// Returns the exit code if the error is an `ExitSignal`
+pub fn exit_code(err: &DynamicError) -> Option<i32> {
+    err.downcast_ref::<ExitSignal>().map(|signal| signal.0)
+}
+
+/// Exit code for source which couldn't be lexed or parsed, `EX_DATAERR` from `sysexits.h`
+pub const EXIT_SYNTAX_ERROR: i32 = 65;
+/// Exit code for errors raised while running a program, `EX_SOFTWARE` from `sysexits.h`
+pub const EXIT_RUNTIME_ERROR: i32 = 70;
+
+/// Returns the exit code for a failed run, telling syntax errors apart from runtime ones
+pub fn failure_code(err: &DynamicError) -> i32 {
+    if err.is::<LexerError>() || err.is::<ParserError>() {
+        return EXIT_SYNTAX_ERROR;
+    }
+
+    EXIT_RUNTIME_ERROR
+}
+
+// --- Warnings ---
+#[derive(Debug, Clone, PartialEq)]
+pub enum Warning {
+    UnusedVariable {
+        name: String,
+        pos: Position
+    },
+    UnreachableCode {
+        keyword: String,
+        pos: Position
+    },
+    MixedIndentation {
+        pos: Position
+    },
+    AssignmentInCondition {
+        pos: Position
+    },
+    ShadowedVariable {
+        name: String,
+        pos: Position,
+        outer: Position
+    },
+    DivisionByZero {
+        pos: Position
+    },
+}
+
+impl Warning {
+    pub fn position(&self) -> &Position {
+        match self {
+            Warning::UnusedVariable { pos, .. } => pos,
+            Warning::UnreachableCode { pos, .. } => pos,
+            Warning::MixedIndentation { pos } => pos,
+            Warning::AssignmentInCondition { pos } => pos,
+            Warning::ShadowedVariable { pos, .. } => pos,
+            Warning::DivisionByZero { pos } => pos,
+        }
+    }
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Warning::UnusedVariable { name, pos } =>
+                write!(f, "Variable '&g&*{}&-&r' is assigned at {} but never used", name, fmt_pos!(pos)),
+            Warning::UnreachableCode { keyword, pos } =>
+                write!(f, "Code after '&m&*{}&-&r' at {} is unreachable", keyword, fmt_pos!(pos)),
+            Warning::MixedIndentation { pos } =>
+                write!(f, "Indentation mixes tabs and spaces at {}", fmt_pos!(pos)),
+            Warning::AssignmentInCondition { pos } =>
+                write!(f, "Assignment used as a condition at {}, did you mean '&y&*==&-&r'? Wrap it in parentheses if intended", fmt_pos!(pos)),
+            Warning::ShadowedVariable { name, pos, outer } =>
+                write!(f, "Parameter '&g&*{}&-&r' at {} shadows the variable assigned at {}", name, fmt_pos!(pos), fmt_pos!(outer)),
+            Warning::DivisionByZero { pos } =>
+                write!(f, "Dividing by a literal &c0&-&r at {} always fails", fmt_pos!(pos)),
         }
     }
 }
@@ -1,12 +1,18 @@
 use super::object::Object;
 
 #[derive(Clone, Debug)]
-pub struct Environment<'a> {
-    name_store: Vec<&'a str>,
+pub struct Environment {
+    name_store: Vec<String>,
     value_store: Vec<Object>,
 }
 
-impl<'a> Environment<'a> {
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Environment {
     pub fn new() -> Self {
         Self {
             name_store: Vec::with_capacity(64),
@@ -23,24 +29,33 @@ impl<'a> Environment<'a> {
         self.value_store.truncate(size);
     }
 
-    pub fn set(&mut self, identifier: &'a str, object: Object) {
-        if self.name_store.contains(&identifier) {
-            if let Some(pos) = self.name_store.iter().rev().position(|name| *name == identifier) {
-                let index = self.value_store.len() - 1 - pos;
-                self.value_store[index] = object;
-                return;
-            }
+    pub fn set(&mut self, identifier: &str, object: Object) {
+        if let Some(pos) = self.name_store.iter().rev().position(|name| name == identifier) {
+            let index = self.value_store.len() - 1 - pos;
+            self.value_store[index] = object;
+            return;
         }
 
-        self.name_store.push(identifier);
+        self.name_store.push(identifier.to_string());
         self.value_store.push(object);
     }
 
-    pub fn get(&self, identifier: &String) -> Option<&Object> {
+    /// Creates a new binding, shadowing any existing one with the same name until the scope is truncated
+    pub fn define(&mut self, identifier: &str, object: Object) {
+        self.name_store.push(identifier.to_string());
+        self.value_store.push(object);
+    }
+
+    pub fn get(&self, identifier: &str) -> Option<&Object> {
         if let Some(pos) = self.name_store.iter().rev().position(|name| name == identifier) {
             return Some(&self.value_store[self.value_store.len() - 1 - pos]);
         }
 
         None
     }
+
+    /// Every binding, oldest first, including ones shadowed by a later binding with the same name
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Object)> {
+        self.name_store.iter().map(String::as_str).zip(&self.value_store)
+    }
 }
@@ -0,0 +1,57 @@
+use crate::{error, errors::EvaluatorError, lexer::token::Position};
+
+use super::{object::{Error, Object}, EvaluatorResult};
+
+/// Reads a property of a value, e.g. `"hi".length`. Like methods, properties are picked by the
+/// type of the receiver
+pub fn get_property(receiver: Object, name: &str, pos: &Position) -> EvaluatorResult<Object> {
+    Ok(match (&receiver, name) {
+        (Object::String(value), "length") => Object::integer(value.chars().count() as i32),
+        (Object::List(elements), "length") => Object::integer(elements.len() as i32),
+
+        _ => error!(EvaluatorError::UnknownProperty {
+            found: receiver.get_type(),
+            name: name.to_string(),
+            pos: pos.to_owned(),
+        })
+    })
+}
+
+/// Calls a method on a value, e.g. `(5).abs()` or `"hi".upper()`. Methods are picked by the
+/// type of the receiver, and none of them take arguments
+pub fn call_method(receiver: Object, name: &str, args: Vec<Object>, pos: &Position) -> EvaluatorResult<Object> {
+    if !args.is_empty() {
+        error!(EvaluatorError::ArgumentCount {
+            name: name.to_string(),
+            min: 0,
+            max: Some(0),
+            found: args.len(),
+            pos: pos.to_owned(),
+        })
+    }
+
+    Ok(match (&receiver, name) {
+        (Object::Integer(value), "abs") => Object::integer(value.checked_abs().ok_or(Error::IntegerOverflow)?),
+        (Object::Integer(value), "to_float") => Object::float(*value as f32),
+
+        (Object::Float(value), "abs") => Object::float(value.abs()),
+        (Object::Float(value), "floor") => Object::float(value.floor()),
+        (Object::Float(value), "ceil") => Object::float(value.ceil()),
+        (Object::Float(value), "round") => Object::float(value.round()),
+
+        (Object::String(value), "upper") => Object::string(&value.to_uppercase()),
+        (Object::String(value), "lower") => Object::string(&value.to_lowercase()),
+        (Object::String(value), "trim") => Object::string(value.trim()),
+        (Object::String(value), "len") => Object::integer(value.chars().count() as i32),
+
+        (Object::List(elements), "len") => Object::integer(elements.len() as i32),
+
+        (_, "to_string") => Object::string(&receiver.to_string()),
+
+        _ => error!(EvaluatorError::UnknownMethod {
+            found: receiver.get_type(),
+            name: name.to_string(),
+            pos: pos.to_owned(),
+        })
+    })
+}
@@ -1,24 +1,97 @@
-use crate::{error, errors::{DynamicError, EvaluatorError}, parser::ast::{ArithmeticOperator, Assignment, BinaryExpression, BlockStatement, Expression, ForStatement, FunctionCallExpression, FunctionDeclareExpression, Identifier, IfStatement, Literal, LogicalOperator, Node, Operator, ReturnStatement, UnaryExpression, WhileStatement}};
+use std::{cell::Cell, panic::{self, AssertUnwindSafe}, rc::Rc, sync::Once, time::{Duration, Instant}};
 
-use self::{environment::Environment, object::{Object, ObjectType}};
+use crate::{error, errors::{DynamicError, EvaluatorError}, lexer::token::Position, parser::ast::{ArithmeticOperator, Assignment, BinaryExpression, BlockStatement, CallExpression, Expression, ForStatement, FunctionCallExpression, FunctionDeclareExpression, Identifier, IfStatement, IndexExpression, Literal, LogicalOperator, MatchArm, MatchExpression, MethodCallExpression, Node, Operator, Parameter, PropertyExpression, ReturnStatement, ThrowStatement, TryStatement, UnaryExpression, WhileStatement}};
+
+use self::{environment::Environment, object::{NativeFunctionObject, Object, ObjectType, DISCARD, FUNCTION_PREFIX}};
 
 pub mod environment;
+pub mod methods;
 pub mod object;
+pub mod optimizer;
 pub mod yaipl_std;
 
 pub type EvaluatorResult<T> = Result<T, DynamicError>;
 pub type StatementResult<T> = EvaluatorResult<(T, bool)>;
 
+#[derive(Debug, Clone)]
+pub struct EvaluatorOptions {
+    /// Treat booleans as `0`/`1` when used as arithmetic operands
+    pub coerce_booleans: bool,
+    /// What integer arithmetic does when the result doesn't fit
+    pub integer_overflow: IntegerOverflow,
+    /// How many loop iterations may run in total, across every loop. Unlimited if `None`
+    pub max_iterations: Option<usize>,
+    /// How long a call to `eval` may run before it is aborted at the next statement or loop
+    /// iteration. Unlimited if `None`
+    pub timeout: Option<Duration>,
+    /// Make `!` require a `Boolean` operand, instead of negating the truthiness of any value
+    pub strict_not: bool,
+    /// How many calls to script functions may be in progress at once, so deep recursion errors
+    /// instead of overflowing the stack
+    pub max_call_depth: usize,
+}
+
+impl Default for EvaluatorOptions {
+    fn default() -> Self {
+        Self {
+            coerce_booleans: false,
+            integer_overflow: IntegerOverflow::default(),
+            max_iterations: None,
+            timeout: None,
+            strict_not: false,
+            // Each call takes around 6KB of stack in debug builds, so this fits in the 8MB the main thread gets
+            max_call_depth: 512,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum IntegerOverflow {
+    /// Overflowing is an error
+    #[default]
+    Checked,
+    /// Wrap around at the bounds, e.g. for hashing
+    Wrapping,
+    /// Clamp to the bounds
+    Saturating,
+}
+
+/// What evaluation does once a step hook returns
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StepSignal {
+    Continue,
+    /// Abort evaluation with `EvaluatorError::Stopped`
+    Stop,
+}
+
+/// Called with the position of each statement before it is evaluated. It may block, e.g. to
+/// wait at a breakpoint, before letting evaluation continue
+pub type StepHook = Box<dyn FnMut(&Position) -> StepSignal>;
+
 pub struct Evaluator<'a> {
-    env: Environment<'a>,
+    pub options: EvaluatorOptions,
+    env: Environment,
     ast: &'a Vec<Node>,
+    step_hook: Option<StepHook>,
+    iterations: usize,
+    deadline: Option<Instant>,
+    /// How many function bodies and blocks used as values are being evaluated. `return` is only
+    /// allowed inside one of them
+    return_depth: usize,
+    call_depth: usize,
 }
 
 impl<'a> Evaluator<'a> {
-    pub fn with_env(ast: &'a Vec<Node>, env: Environment<'a>) -> Self {
+    pub fn with_env(ast: &'a Vec<Node>, env: Environment) -> Self {
         Self {
+            options: EvaluatorOptions::default(),
             env,
-            ast
+            ast,
+            step_hook: None,
+            iterations: 0,
+            deadline: None,
+            return_depth: 0,
+            call_depth: 0,
         }
     }
 
@@ -29,10 +102,32 @@ impl<'a> Evaluator<'a> {
         Self::with_env(ast, env)
     }
 
+    /// Like `new`, but first folds the constant expressions in `ast`, e.g. `2 * 3 + 1` into `7`
+    pub fn folded(ast: &'a mut Vec<Node>) -> Self {
+        optimizer::fold_constants(ast);
+        Self::new(ast)
+    }
+
+    /// Borrows the statements of the program returned by `Parser::parse`, so the caller doesn't
+    /// need to take them out of the node first
+    pub fn from_ref(program: &'a Node) -> Result<Self, DynamicError> {
+        if let Node::Program(ast) = program {
+            return Ok(Self::new(ast));
+        }
+
+        error!("AST is not a program node.");
+    }
+
     pub fn eval(&mut self) -> Result<Object, DynamicError> {
         let mut result: (Object, bool) = (Object::void(), false);
+        self.deadline = self.options.timeout.map(|timeout| Instant::now() + timeout);
         
         for node in self.ast {
+            // Empty statements have no value, so they keep the result of the last statement which did
+            if matches!(node, Node::EmptyStatement(_)) {
+                continue;
+            }
+
             result = self.eval_statement(node)?;
             if result.1 {
                 break;
@@ -42,7 +137,54 @@ impl<'a> Evaluator<'a> {
         Ok(result.0)
     }
 
-    fn eval_statement(&mut self, node: &'a Node) -> StatementResult<Object> {
+    /// Clears every binding made by evaluated code or the host, leaving only the built-ins,
+    /// and restarts the count of loop iterations
+    pub fn reset(&mut self) {
+        self.env = Environment::new();
+        yaipl_std::initialize(&mut self.env);
+        self.iterations = 0;
+    }
+
+    /// Gives back the environment, so its bindings can be carried over into another evaluation
+    pub fn into_env(self) -> Environment {
+        self.env
+    }
+
+    /// Binds a value in the global scope before evaluation, replacing any existing binding
+    pub fn define_global(&mut self, name: &str, value: Object) {
+        self.env.set(name, value);
+    }
+
+    /// Makes a Rust function callable from scripts under `name`, like a built-in. It takes any
+    /// number of arguments. If it panics, the call fails with `EvaluatorError::NativePanic`, and the
+    /// first native call wraps the process's panic hook to keep that panic quiet
+    pub fn register(&mut self, name: &str, function: impl Fn(Vec<Object>) -> EvaluatorResult<Object> + 'static) {
+        let name = format!("{}{}", FUNCTION_PREFIX, name);
+        let function = NativeFunctionObject(name.to_owned(), vec![String::from("...args")], Rc::new(move |_, args, _| function(args)));
+
+        self.env.set(&name, Object::native_function(function));
+    }
+
+    pub fn on_step(&mut self, hook: impl FnMut(&Position) -> StepSignal + 'static) {
+        self.step_hook = Some(Box::new(hook));
+    }
+
+    fn eval_statement(&mut self, node: &Node) -> StatementResult<Object> {
+        if let Some(pos) = node.position() {
+            self.check_deadline(pos)?;
+        }
+
+        if let (Some(hook), Some(pos)) = (self.step_hook.as_mut(), node.position()) {
+            if hook(pos) == StepSignal::Stop {
+                error!(EvaluatorError::Stopped { pos: pos.to_owned() })
+            }
+        }
+
+        self.eval_node(node)
+    }
+
+    /// Evaluates a node without calling the step hook, e.g. for the value of an assignment
+    fn eval_node(&mut self, node: &Node) -> StatementResult<Object> {
         match node {
             Node::BlockStatement(block) => self.eval_block(block),
             Node::BreakStatement(_) => Ok((Object::void(), true)),
@@ -54,12 +196,14 @@ impl<'a> Evaluator<'a> {
             Node::ReturnStatement(statement) => self.eval_return(statement),
             Node::WhileStatement(statement) => self.eval_while(statement),
             Node::ForStatement(statement) => self.eval_for(statement),
+            Node::ThrowStatement(statement) => self.eval_throw(statement),
+            Node::TryStatement(statement) => self.eval_try(statement),
             _ => error!(format!("Not implemented statement {:#?}", node))
         }
     }
 
-    fn eval_for(&mut self, statement: &'a ForStatement) -> StatementResult<Object> {
-        let ForStatement(setter, condition, assignment, body) = statement;
+    fn eval_for(&mut self, statement: &ForStatement) -> StatementResult<Object> {
+        let ForStatement(setter, condition, assignment, body, pos) = statement;
 
         let setter = match setter {
             Expression::AssignmentExpr(setter) => setter,
@@ -68,13 +212,6 @@ impl<'a> Evaluator<'a> {
             })
         };
 
-        let condition = match condition {
-            Expression::BinaryExpr(condition) => condition,
-            _ => error!(EvaluatorError::InvalidExpression { 
-                expected: String::from("BinaryExpr")
-            })
-        };
-
         let assignment = match assignment {
             Expression::AssignmentExpr(assignment) => assignment,
             _ => error!(EvaluatorError::InvalidExpression { 
@@ -86,7 +223,8 @@ impl<'a> Evaluator<'a> {
 
         let scope_size = self.new_scope();
         self.eval_assignment_expression(setter)?;
-        while self.eval_binary_expression(condition)?.as_boolean().expect("Couldn't take as boolean") {
+        while self.eval_expression(condition)?.is_truthy() {
+            self.count_iteration(pos)?;
             result = self.eval_block(body)?;
             
             if result.1 {
@@ -100,12 +238,13 @@ impl<'a> Evaluator<'a> {
         Ok(result)
     }
 
-    fn eval_while(&mut self, statement: &'a WhileStatement) -> StatementResult<Object> {
-        let WhileStatement(condition, block) = statement;
+    fn eval_while(&mut self, statement: &WhileStatement) -> StatementResult<Object> {
+        let WhileStatement(condition, block, pos) = statement;
         let mut result = (Object::void(), false);
 
         let scope_size = self.new_scope();
-        while self.eval_expression(condition)?.as_boolean().expect("Couldn't take as boolean") {
+        while self.eval_expression(condition)?.is_truthy() {
+            self.count_iteration(pos)?;
             result = self.eval_block(block)?;
             if result.1 {
                 break;
@@ -116,10 +255,41 @@ impl<'a> Evaluator<'a> {
         Ok(result)
     }
 
-    fn eval_return(&mut self, statement: &'a ReturnStatement) -> StatementResult<Object> {
-        let ReturnStatement(expression) = statement;
+    /// Counts an iteration of the loop at `pos`, failing once there have been more than `max_iterations`
+    fn count_iteration(&mut self, pos: &Position) -> EvaluatorResult<()> {
+        // Checked here too, as a loop with an empty body never reaches a statement
+        self.check_deadline(pos)?;
+        self.iterations += 1;
+
+        match self.options.max_iterations {
+            Some(limit) if self.iterations > limit => error!(EvaluatorError::IterationLimit {
+                limit,
+                pos: pos.to_owned(),
+            }),
+            _ => Ok(())
+        }
+    }
+
+    fn check_deadline(&self, pos: &Position) -> EvaluatorResult<()> {
+        match (self.deadline, self.options.timeout) {
+            (Some(deadline), Some(timeout)) if Instant::now() >= deadline => error!(EvaluatorError::Timeout {
+                timeout,
+                pos: pos.to_owned(),
+            }),
+            _ => Ok(())
+        }
+    }
+
+    fn eval_return(&mut self, statement: &ReturnStatement) -> StatementResult<Object> {
+        let ReturnStatement(expression, pos) = statement;
         let mut result = Object::void();
 
+        // Inside blocks used as values `return` gives the block its value, e.g. in an `if`
+        // expression, but anywhere else at program scope there is nothing for it to return from
+        if self.return_depth == 0 {
+            error!(EvaluatorError::ReturnOutsideFunction { pos: pos.to_owned() })
+        }
+
         if let Some(expression) = expression {
             result = self.eval_expression(expression)?;
         }
@@ -127,39 +297,107 @@ impl<'a> Evaluator<'a> {
         Ok((result, true))
     }
 
-    fn eval_if(&mut self, statement: &'a IfStatement) -> StatementResult<Object> {
-        let IfStatement(condition, block, elif) = statement;
-        let condition = self.eval_expression(condition)?;
+    fn eval_throw(&mut self, statement: &ThrowStatement) -> StatementResult<Object> {
+        let ThrowStatement(expression, pos) = statement;
+        let value = self.eval_expression(expression)?;
 
-        if condition.is(ObjectType::Boolean) {
-            let scope_size = self.new_scope();
+        error!(EvaluatorError::Thrown {
+            value,
+            pos: pos.to_owned(),
+        })
+    }
 
-            let result = if condition.as_boolean().expect("Couldn't take as boolean") {
-                self.eval_block(block)
-            } else if let Some(elif) = elif {
-                self.eval_statement(&elif)
-            } else {
-                Ok((Object::void(), false))
-            };
+    /// Runs the body, and if a value is thrown from it, runs the handler with the value bound to its name.
+    /// Any other error keeps propagating
+    fn eval_try(&mut self, statement: &TryStatement) -> StatementResult<Object> {
+        let TryStatement(body, identifier, handler, _) = statement;
+        let scope_size = self.new_scope();
+
+        let result = self.eval_block(body);
+
+        // The throw may come from inside function calls whose scopes were never destroyed
+        self.destroy_scope(scope_size);
+
+        let error = match result {
+            Err(error) => error,
+            result => return result,
+        };
 
-            self.destroy_scope(scope_size);
-            return result;
+        let value = match error.downcast::<EvaluatorError>() {
+            Ok(error) => match *error {
+                EvaluatorError::Thrown { value, .. } => value,
+                error => error!(error),
+            },
+            Err(error) => return Err(error),
         };
 
-        Ok((Object::void(), false))
+        if identifier.0 != DISCARD {
+            self.env.define(&identifier.0, value);
+        }
+
+        let result = self.eval_block(handler);
+        self.destroy_scope(scope_size);
+        result
+    }
+
+    fn eval_if(&mut self, statement: &IfStatement) -> StatementResult<Object> {
+        let IfStatement(condition, block, elif, _) = statement;
+        let condition = self.eval_expression(condition)?;
+        let scope_size = self.new_scope();
+
+        let result = if condition.is_truthy() {
+            self.eval_block(block)
+        } else if let Some(elif) = elif {
+            self.eval_statement(elif)
+        } else {
+            Ok((Object::void(), false))
+        };
+
+        self.destroy_scope(scope_size);
+        result
     }
 
-    fn eval_expression(&mut self, expression: &'a Expression) -> EvaluatorResult<Object> {
+    /// Evaluates a single expression against the current scope, e.g. for watching a value after `eval`
+    pub fn eval_expression(&mut self, expression: &Expression) -> EvaluatorResult<Object> {
         Ok(match expression {
             Expression::AssignmentExpr(expression) => self.eval_assignment_expression(expression)?,
             Expression::BinaryExpr(expression) => self.eval_binary_expression(expression)?,
-            Expression::BlockExpr(expression) => (self.eval_block(expression)?).0,
+            Expression::BlockExpr(expression) => self.returning(|evaluator| evaluator.eval_block(expression))?.0,
             Expression::FunctionCallExpr(expression) => self.eval_func_call_expression(expression)?,
+            Expression::CallExpr(expression) => self.eval_call_expression(expression)?,
+            Expression::IndexExpr(expression) => self.eval_index_expression(expression)?,
+            Expression::MethodCallExpr(expression) => self.eval_method_call_expression(expression)?,
+            Expression::PropertyExpr(PropertyExpression(receiver, property, pos)) => {
+                let receiver = self.eval_expression(receiver)?;
+                methods::get_property(receiver, &property.0, pos)?
+            },
             Expression::FunctionDeclareExpr(expression) => self.eval_func_declare_expression(expression)?,
             Expression::GroupExpr(expression) => self.eval_expression(expression)?,
-            Expression::IdentifierExpr(expression) => self.eval_identifier(expression)?,
+            Expression::IdentifierExpr(expression, pos) => self.eval_identifier(expression, pos)?,
             Expression::LiteralExpr(expression) => self.eval_literal(expression)?,
             Expression::UnaryExpr(expression) => self.eval_unary_expression(expression)?,
+            Expression::MatchExpr(expression) => self.eval_match_expression(expression)?,
+        })
+    }
+
+    fn eval_match_expression(&mut self, expression: &MatchExpression) -> EvaluatorResult<Object> {
+        let MatchExpression(scrutinee, arms, pos) = expression;
+        let value = self.eval_expression(scrutinee)?;
+
+        for MatchArm(pattern, body) in arms {
+            let matched = match pattern {
+                Some(pattern) => self.eval_literal(pattern)?.equals(&value),
+                None => true
+            };
+
+            if matched {
+                return self.eval_expression(body);
+            }
+        }
+
+        error!(EvaluatorError::NoMatch {
+            value: value.to_string(),
+            pos: pos.to_owned(),
         })
     }
 
@@ -171,53 +409,201 @@ impl<'a> Evaluator<'a> {
         self.env.truncate(size);
     }
 
-    fn eval_func_call_expression(&mut self, expression: &'a FunctionCallExpression) -> EvaluatorResult<Object> {
-        let FunctionCallExpression(identifier, args) = expression;
-        let object = self.env.get(&identifier.0);
+    fn eval_func_call_expression(&mut self, expression: &FunctionCallExpression) -> EvaluatorResult<Object> {
+        let FunctionCallExpression(identifier, args, pos) = expression;
+
+        // Fall back to a plain variable so calling a non-function value reports what it is
+        let object = self.env.get(&identifier.0)
+            .or_else(|| self.env.get(identifier.0.trim_start_matches(FUNCTION_PREFIX)));
 
         if let Some(object) = object {
             let object = object.to_owned();
+            let args = self.eval_arguments(args)?;
+
+            return self.call_object(object, args, pos);
+        }
+        
+        error!(EvaluatorError::ObjectNotFound {
+            name: identifier.0.trim_start_matches(FUNCTION_PREFIX).to_string(),
+            pos: pos.to_owned(),
+        })
+    }
+
+    fn eval_call_expression(&mut self, expression: &CallExpression) -> EvaluatorResult<Object> {
+        let CallExpression(callee, args, pos) = expression;
+
+        let object = self.eval_expression(callee)?;
+        let args = self.eval_arguments(args)?;
+
+        self.call_object(object, args, pos)
+    }
+
+    fn eval_method_call_expression(&mut self, expression: &MethodCallExpression) -> EvaluatorResult<Object> {
+        let MethodCallExpression(receiver, method, args, pos) = expression;
+
+        let receiver = self.eval_expression(receiver)?;
+        let args = self.eval_arguments(args)?;
+
+        methods::call_method(receiver, &method.0, args, pos)
+    }
+
+    fn eval_arguments(&mut self, args: &[Expression]) -> EvaluatorResult<Vec<Object>> {
+        let mut built_args: Vec<Object> = Vec::with_capacity(args.len());
+        for arg in args {
+            built_args.push(self.eval_expression(arg)?);
+        }
+
+        Ok(built_args)
+    }
+
+    fn call_object(&mut self, object: Object, built_args: Vec<Object>, pos: &Position) -> EvaluatorResult<Object> {
+        let result = match object.get_type() {
+            ObjectType::Function => {
+                let function = object.as_function().expect("Couldn't take as function");
+
+                let required = function.1.iter().filter(|parameter| parameter.1.is_none() && !parameter.2).count();
+                let max = match function.1.last() {
+                    Some(parameter) if parameter.2 => None,
+                    _ => Some(function.1.len()),
+                };
+
+                if built_args.len() < required || max.is_some_and(|max| built_args.len() > max) {
+                    error!(EvaluatorError::ArgumentCount {
+                        name: function.0.0.trim_start_matches(FUNCTION_PREFIX).to_string(),
+                        min: required,
+                        max,
+                        found: built_args.len(),
+                        pos: pos.to_owned(),
+                    })
+                }
+
+                if self.call_depth >= self.options.max_call_depth {
+                    error!(EvaluatorError::CallDepthLimit {
+                        limit: self.options.max_call_depth,
+                        pos: pos.to_owned(),
+                    })
+                }
+
+                self.call_depth += 1;
+                let result = self.call_function(function, built_args);
+                self.call_depth -= 1;
+                result?
+            },
+            ObjectType::NativeFunction => {
+                let function = object.as_native_function().expect("Couldn't take as natve function");
+
+                // Work a native function counts against the iteration limit is added to the script's loops
+                NATIVE_ITERATIONS.with(|iterations| iterations.set(self.options.max_iterations.map(|limit| (self.iterations, limit))));
+
+                // A panicking host function becomes an error, rather than taking the interpreter down with it
+                let result = catch_native_panic(|| (function.2)(&mut self.env, built_args, pos));
+
+                if let Some((used, _)) = NATIVE_ITERATIONS.with(Cell::take) {
+                    self.iterations = used;
+                }
+                match result {
+                    Ok(result) => (result?, false),
+                    Err(payload) => error!(EvaluatorError::NativePanic {
+                        name: function.0.trim_start_matches(FUNCTION_PREFIX).to_string(),
+                        message: payload.downcast_ref::<&str>().map(|message| message.to_string())
+                            .or_else(|| payload.downcast_ref::<String>().cloned())
+                            .unwrap_or_default(),
+                        pos: pos.to_owned(),
+                    })
+                }
+            },
+            _ => error!(EvaluatorError::NotCallable { 
+                found: object.get_type(),
+                pos: pos.to_owned(),
+            })
+        };
+
+        Ok(result.0)
+    }
+
+    /// Binds the arguments to the function's parameters in a new scope and evaluates its body
+    fn call_function(&mut self, function: &FunctionDeclareExpression, built_args: Vec<Object>) -> StatementResult<Object> {
+        let scope_size = self.new_scope();
+        let mut built_args = built_args.into_iter();
 
-            let mut built_args: Vec<Object> = Vec::new();
-            for arg in args {
-                built_args.push(self.eval_expression(arg)?);
+        for Parameter(identifier, default, rest, _) in &function.1 {
+            if *rest {
+                let remaining = built_args.by_ref().collect();
+                if identifier.0 != DISCARD {
+                    self.env.define(&identifier.0, Object::list(remaining));
+                }
+                break;
             }
-            
-            let result = match object.get_type() {
-                ObjectType::Function => {
-                    let function = object.as_function().expect("Couldn't take as function");
-            
-                    let scope_size = self.new_scope();
-                    for (index, arg) in built_args.iter().enumerate() {
-                        let identifier = &function.1[index];
-                        self.env.set(&identifier.0, arg.to_owned());
-                    }
-                    
-                    let result = self.eval_block(&function.2)?;
-                    self.destroy_scope(scope_size);
-                    result
-                },
-                ObjectType::NativeFunction => {
-                    let function = object.as_native_function().expect("Couldn't take as natve function");
-                    
-                    ((function.2)(&mut self.env, built_args), false)
-                },
-                _ => error!(EvaluatorError::InvalidType { 
-                    expected: vec![ObjectType::Function, ObjectType::NativeFunction],
-                    found: object.get_type(),
-                })
+
+            // Defaults are evaluated at call time, so they can refer to earlier parameters
+            let value = match (built_args.next(), default) {
+                (Some(arg), _) => arg,
+                (None, Some(default)) => self.eval_expression(default)?,
+                (None, None) => Object::void(),
             };
 
-            return Ok(result.0);
+            if identifier.0 != DISCARD {
+                self.env.define(&identifier.0, value);
+            }
+        }
+
+        let result = self.returning(|evaluator| evaluator.eval_block(&function.2))?;
+        self.destroy_scope(scope_size);
+        Ok(result)
+    }
+
+    fn eval_index_expression(&mut self, expression: &IndexExpression) -> EvaluatorResult<Object> {
+        let IndexExpression(target, index, pos) = expression;
+
+        let target = self.eval_expression(target)?;
+        let index = self.eval_expression(index)?;
+
+        let Some(index) = index.as_integer() else {
+            error!(EvaluatorError::InvalidType {
+                expected: vec![ObjectType::Integer],
+                found: index.get_type(),
+            })
+        };
+
+        let (element, length) = match &target {
+            Object::List(elements) => (usize::try_from(index).ok().and_then(|index| elements.get(index)).cloned(), elements.len()),
+            Object::String(string) => (
+                usize::try_from(index).ok().and_then(|index| string.chars().nth(index)).map(|char| Object::string(&char.to_string())),
+                string.chars().count()
+            ),
+            _ => error!(EvaluatorError::InvalidType {
+                expected: vec![ObjectType::List, ObjectType::String],
+                found: target.get_type(),
+            })
+        };
+
+        match element {
+            Some(element) => Ok(element),
+            None => error!(EvaluatorError::IndexOutOfBounds {
+                index,
+                length,
+                pos: pos.to_owned(),
+            })
         }
-        
-        error!(EvaluatorError::ObjectNotFound { name: identifier.0.to_owned() })
     }
 
-    fn eval_block(&mut self, expression: &'a BlockStatement) -> StatementResult<Object> {
+    /// Runs `eval` somewhere `return` can give a value to, i.e. a function body or a block used as a value
+    fn returning<T>(&mut self, eval: impl FnOnce(&mut Self) -> EvaluatorResult<T>) -> EvaluatorResult<T> {
+        self.return_depth += 1;
+        let result = eval(self);
+        self.return_depth -= 1;
+
+        result
+    }
+
+    fn eval_block(&mut self, expression: &BlockStatement) -> StatementResult<Object> {
         let mut result = (Object::void(), false);
         
         for statement in &expression.0 {
+            if matches!(statement, Node::EmptyStatement(_)) {
+                continue;
+            }
+
             result = self.eval_statement(statement)?;
             if result.1 {
                 break;
@@ -227,54 +613,75 @@ impl<'a> Evaluator<'a> {
         Ok(result)
     }
 
-    fn eval_func_declare_expression(&mut self, expression: &'a FunctionDeclareExpression) -> EvaluatorResult<Object> {
+    fn eval_func_declare_expression(&mut self, expression: &FunctionDeclareExpression) -> EvaluatorResult<Object> {
         let object = Object::function(expression);
         self.env.set(&expression.0.0, object);
 
         Ok(Object::void())
     }
 
-    fn eval_identifier(&self, expression: &Identifier) -> EvaluatorResult<Object> {
+    fn eval_identifier(&self, expression: &Identifier, pos: &Position) -> EvaluatorResult<Object> {
         let Identifier(identifier) = expression;
         
-        match self.env.get(identifier) {
+        // Functions are stored under a prefixed name, reading them by their plain name yields the function itself
+        let object = self.env.get(identifier)
+            .or_else(|| self.env.get(&format!("{}{}", FUNCTION_PREFIX, identifier)));
+
+        match object {
             Some(object) => Ok(object.to_owned()),
-            None => error!(EvaluatorError::ObjectNotFound { name: identifier.to_owned() })
+            None => error!(EvaluatorError::ObjectNotFound {
+                name: identifier.to_owned(),
+                pos: pos.to_owned(),
+            })
         }
     }
 
-    fn eval_assignment_expression(&mut self, expression: &'a Assignment) -> EvaluatorResult<Object> {
-        let Assignment(identifier, literal) = expression;
+    fn eval_assignment_expression(&mut self, expression: &Assignment) -> EvaluatorResult<Object> {
+        let Assignment(identifier, literal, _) = expression;
 
-        let value = self.eval_statement(&literal)?.0;
-        self.env.set(&identifier.0, value);
+        let value = self.returning(|evaluator| evaluator.eval_node(literal))?.0;
+        if identifier.0 != DISCARD {
+            self.env.set(&identifier.0, value);
+        }
         Ok(Object::void())
     }
 
-    fn eval_literal(&self, expression: &Literal) -> EvaluatorResult<Object> {
+    fn eval_literal(&mut self, expression: &Literal) -> EvaluatorResult<Object> {
         Ok(match expression {
             Literal::Integer(num) => Object::integer(num.0),
             Literal::Boolean(bool) => Object::boolean(bool.0),
             Literal::Float(num) => Object::float(num.0),
             Literal::String(str) => Object::string(&str.0),
-            Literal::List(list) => Object::list(&list.0),
+            Literal::List(list) => {
+                let mut elements: Vec<Object> = Vec::with_capacity(list.0.len());
+                for element in &list.0 {
+                    elements.push(self.eval_expression(element)?);
+                }
+
+                Object::list(elements)
+            },
             Literal::Null => Object::null(),
         })
     }
 
-    fn eval_unary_expression(&mut self, expression: &'a UnaryExpression) -> EvaluatorResult<Object> {
+    fn eval_unary_expression(&mut self, expression: &UnaryExpression) -> EvaluatorResult<Object> {
         let UnaryExpression(operator, expr) = expression;
 
         let object = self.eval_expression(expr)?;
         if operator == &Operator::Logical(LogicalOperator::Not) {
-            if object.is(ObjectType::Boolean) {
-                return Ok(object);
+            if self.options.strict_not && !object.is(ObjectType::Boolean) {
+                error!(EvaluatorError::InvalidType {
+                    expected: vec![ObjectType::Boolean],
+                    found: object.get_type(),
+                })
             }
+
+            return Ok(Object::boolean(!object.is_truthy()));
         }
 
         if operator == &Operator::Arithmetic(ArithmeticOperator::Minus) {
             return Ok(match object.get_type() {
-                ObjectType::Integer => Object::integer(-object.as_integer().expect("Couldn't take as integer")),
+                ObjectType::Integer => object::integer_arithmetic(0, &ArithmeticOperator::Minus, object.as_integer().expect("Couldn't take as integer"), self.options.integer_overflow)?,
                 ObjectType::Float => Object::float(-object.as_f32().expect("Couldn't take as float")),
                 _ => error!(EvaluatorError::InvalidType { 
                     expected: vec![ObjectType::Integer, ObjectType::Float],
@@ -288,12 +695,53 @@ impl<'a> Evaluator<'a> {
         })
     }
 
-    fn eval_binary_expression(&mut self, expression: &'a BinaryExpression) -> EvaluatorResult<Object> {
-        let BinaryExpression(left, operator, right) = expression;
+    fn eval_binary_expression(&mut self, expression: &BinaryExpression) -> EvaluatorResult<Object> {
+        let BinaryExpression(left, operator, right, _, (left_pos, right_pos)) = expression;
 
-        let lhs = self.eval_expression(left)?;
-        let rhs = self.eval_expression(right)?;
+        let mut lhs = self.eval_expression(left)?;
+
+        // `&&` and `||` return whichever operand decides the result, so the right side is only
+        // evaluated when the left one doesn't, e.g. `false && f()` never calls `f`
+        if let Operator::Logical(operator @ (LogicalOperator::And | LogicalOperator::Or)) = operator {
+            if lhs.is_truthy() == (operator == &LogicalOperator::Or) {
+                return Ok(lhs);
+            }
+
+            return self.eval_expression(right);
+        }
+
+        let mut rhs = self.eval_expression(right)?;
+
+        if self.options.coerce_booleans && matches!(operator, Operator::Arithmetic(_))
+            && !lhs.is(ObjectType::String) && !rhs.is(ObjectType::String) {
+            lhs = lhs.coerce_boolean();
+            rhs = rhs.coerce_boolean();
+        }
 
+        if let (Operator::Arithmetic(operator), Object::Integer(lhs), Object::Integer(rhs)) = (operator, &lhs, &rhs) {
+            return Ok(object::integer_arithmetic(*lhs, operator, *rhs, self.options.integer_overflow)?);
+        }
+
+        let types = (lhs.get_type(), rhs.get_type());
+        Self::eval_operator(lhs, operator, rhs).map_err(|err| match err.downcast_ref::<object::Error>() {
+            Some(object::Error::TypeError(_)) => {
+                // Blame the first operand which isn't a number, e.g. the string in `"a" - 1`
+                let is_number = |object_type: &ObjectType| matches!(object_type, ObjectType::Integer | ObjectType::Float);
+                let pos = if is_number(&types.0) { right_pos } else { left_pos };
+
+                EvaluatorError::OperandTypes {
+                    operator: operator.symbol().to_string(),
+                    pos: pos.to_owned(),
+                    left: (types.0, left_pos.to_owned()),
+                    right: (types.1, right_pos.to_owned()),
+                }.into()
+            },
+            _ => err
+        })
+    }
+
+    /// Applies a binary operator to two already evaluated operands
+    pub(crate) fn eval_operator(lhs: Object, operator: &Operator, rhs: Object) -> EvaluatorResult<Object> {
         let result = match operator {
             Operator::Logical(op) => match op {
                 LogicalOperator::Or => lhs.or(rhs),
@@ -305,6 +753,7 @@ impl<'a> Evaluator<'a> {
                 LogicalOperator::GreaterThanEqual => lhs.greater_than_equal(rhs),
                 LogicalOperator::LesserThan => lhs.lesser_than(rhs),
                 LogicalOperator::LesserThanEqual => lhs.lesser_than_equal(rhs),
+                LogicalOperator::In => lhs.is_in(rhs),
             },
             Operator::Arithmetic(op) => match op {
                 ArithmeticOperator::Plus => lhs.add(rhs),
@@ -319,8 +768,914 @@ impl<'a> Evaluator<'a> {
 
         match result {
             Ok(object) => Ok(object),
-            Err(err) => return Err(err.into())
+            Err(err) => Err(err.into())
+        }
+    }
+}
+
+thread_local! {
+    static IN_NATIVE_CALL: Cell<bool> = const { Cell::new(false) };
+    /// Iterations counted so far and the limit, while a native function runs under `max_iterations`
+    static NATIVE_ITERATIONS: Cell<Option<(usize, usize)>> = const { Cell::new(None) };
+}
+
+/// Counts `count` iterations of work done by a native function, e.g. the elements `range` builds,
+/// against the iteration limit of the evaluator calling it. Does nothing if there is no limit
+pub fn count_native_iterations(count: usize, pos: &Position) -> EvaluatorResult<()> {
+    let Some((used, limit)) = NATIVE_ITERATIONS.with(Cell::get) else {
+        return Ok(());
+    };
+
+    if count > limit - used {
+        error!(EvaluatorError::IterationLimit {
+            limit,
+            pos: pos.to_owned(),
+        })
+    }
+
+    NATIVE_ITERATIONS.with(|iterations| iterations.set(Some((used + count, limit))));
+    Ok(())
+}
+
+static QUIET_NATIVE_PANICS: Once = Once::new();
+
+/// Runs a native function, catching any panic from it. The panic hook stays quiet for these, as the
+/// error they become already reports the message, but keeps working for panics anywhere else.
+///
+/// The panic hook is process-wide. The first native call wraps whichever hook is installed at that
+/// point and never restores it, as swapping hooks per call would race with other threads. A host
+/// which sets its own hook later replaces the wrapper, so native panics are printed by that hook
+/// but still become errors
+fn catch_native_panic<T>(call: impl FnOnce() -> T) -> std::thread::Result<T> {
+    QUIET_NATIVE_PANICS.call_once(|| {
+        let hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !IN_NATIVE_CALL.with(Cell::get) {
+                hook(info);
+            }
+        }));
+    });
+
+    let outer = IN_NATIVE_CALL.with(|flag| flag.replace(true));
+    let result = panic::catch_unwind(AssertUnwindSafe(call));
+    IN_NATIVE_CALL.with(|flag| flag.set(outer));
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{errors::ParserError, parser::ast::{EmptyStatement, ExpressionStatement, IntegerLiteral}, testing::{parse, run, run_with, statements}};
+
+    use super::*;
+
+    fn run_folded(input: &str) -> EvaluatorResult<Object> {
+        let mut ast = statements(input);
+        Evaluator::folded(&mut ast).eval()
+    }
+
+    #[test]
+    fn while_condition_uses_truthiness() {
+        let result = run("i = 0; while i < 3 && 1 { i = i + 1; }; i").unwrap();
+        assert_eq!(result, Object::integer(3));
+    }
+
+    #[test]
+    fn for_condition_uses_truthiness() {
+        let result = run("n = 0; for i = 0; i < 3 && \"yes\"; i += 1 { n = n + 1; }; n").unwrap();
+        assert_eq!(result, Object::integer(3));
+    }
+
+    #[test]
+    fn if_condition_uses_truthiness() {
+        assert_eq!(run("x = false; if x || 0 { 1; } else { 2; }").unwrap(), Object::integer(2));
+        assert_eq!(run("x = false; if x || 5 { 1; } else { 2; }").unwrap(), Object::integer(1));
+        assert_eq!(run("if [] { 1; } else { 2; }").unwrap(), Object::integer(2));
+    }
+
+    #[test]
+    fn folding_replaces_constant_expressions() {
+        let mut ast = statements("2 * 3 + 1;");
+        optimizer::fold_constants(&mut ast);
+
+        assert!(matches!(
+            &ast[0],
+            Node::ExpressionStatement(ExpressionStatement(Expression::LiteralExpr(Literal::Integer(IntegerLiteral(7))), _))
+        ));
+    }
+
+    #[test]
+    fn folded_and_unfolded_results_match() {
+        let programs = [
+            "2 * 3 + 1",
+            "(1 + 2) * -3",
+            "10 / 4 + 0.5",
+            "2 ^ 10 - 1",
+            "\"a\" + 1 + 2",
+            "1 == 1.0 && 2 < 3",
+            "0 || \"fallback\"",
+            "x = 4; x * (2 + 3)",
+            "[1 + 1, 2 * 2]",
+        ];
+
+        for program in programs {
+            assert_eq!(run_folded(program).unwrap(), run(program).unwrap(), "{}", program);
         }
+    }
+
+    #[test]
+    fn folding_leaves_failing_expressions_to_the_evaluator() {
+        for program in ["1 / 0", "5 % 0", "2147483647 + 1", "\"a\" - 1"] {
+            let folded = run_folded(program).unwrap_err();
+            let unfolded = run(program).unwrap_err();
+
+            assert_eq!(folded.to_string(), unfolded.to_string(), "{}", program);
+        }
+    }
 
+    fn is_return_outside_function(result: EvaluatorResult<Object>) -> bool {
+        matches!(result.unwrap_err().downcast_ref(), Some(EvaluatorError::ReturnOutsideFunction { .. }))
+    }
+
+    #[test]
+    fn return_at_program_scope_errors() {
+        assert!(is_return_outside_function(run("return 5;")));
+        assert!(is_return_outside_function(run("if true { return 5; }")));
+        assert!(is_return_outside_function(run("while true { return 5; }")));
+    }
+
+    #[test]
+    fn return_gives_functions_and_block_values_their_value() {
+        assert_eq!(run("f = () { if true { return 5; }; return 6; }; f()").unwrap(), Object::integer(5));
+        assert_eq!(run("x = if true { return 5; } else { return 6; }; x").unwrap(), Object::integer(5));
+    }
+
+    #[test]
+    fn native_panic_becomes_error() {
+        let program = parse("boom()").unwrap();
+
+        let mut evaluator = Evaluator::from_ref(&program).unwrap();
+        evaluator.register("boom", |_| panic!("host failure"));
+
+        match evaluator.eval().unwrap_err().downcast_ref() {
+            Some(EvaluatorError::NativePanic { name, message, .. }) => {
+                assert_eq!(name, "boom");
+                assert_eq!(message, "host failure");
+            },
+            _ => panic!("expected a native panic"),
+        }
+    }
+
+    #[test]
+    fn try_catches_thrown_value() {
+        let result = run("try { throw \"oops\"; } catch e { e; }").unwrap();
+        assert_eq!(result, Object::string("oops"));
+    }
+
+    #[test]
+    fn try_destroys_its_scope_on_success() {
+        let error = run("try { y = 1; } catch _ {}; y").unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(EvaluatorError::ObjectNotFound { .. })));
+    }
+
+    #[test]
+    fn uncaught_throw_is_runtime_error() {
+        let error = run("f = () { throw 42; }; f()").unwrap_err();
+        match error.downcast_ref() {
+            Some(EvaluatorError::Thrown { value, .. }) => assert_eq!(*value, Object::integer(42)),
+            _ => panic!("expected a thrown value, got {}", error),
+        }
+    }
+
+    #[test]
+    fn else_if_chain_selects_middle_branch() {
+        let program = "x = 2;\nif x == 1 { \"one\"; } else if x == 2 { \"two\"; } else { \"other\"; }";
+        assert_eq!(run(program).unwrap(), Object::string("two"));
+
+        let program = "x = 2;\nif x == 1 { \"one\"; } elif x == 2 { \"two\"; } else { \"other\"; }";
+        assert_eq!(run(program).unwrap(), Object::string("two"));
+    }
+
+    #[test]
+    fn coerced_booleans_add_as_integers() {
+        let options = EvaluatorOptions { coerce_booleans: true, ..Default::default() };
+        assert_eq!(run_with(options, "true + true + 1").unwrap(), Object::integer(3));
+    }
+
+    #[test]
+    fn booleans_in_arithmetic_error_by_default() {
+        let error = run("true + 1").unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(EvaluatorError::OperandTypes { .. })), "{}", error);
+    }
+
+    #[test]
+    fn integers_equal_floats_numerically() {
+        assert_eq!(run("1 == 1.0").unwrap(), Object::boolean(true));
+        assert_eq!(run("1 != 1.5").unwrap(), Object::boolean(true));
+        assert!(Object::integer(2).equals(&Object::float(2.0)));
+    }
+
+    #[test]
+    fn integers_too_large_for_a_float_are_compared_exactly() {
+        assert_eq!(run("16777217 == 16777216.0").unwrap(), Object::boolean(false));
+        assert_eq!(run("16777216 == 16777216.0").unwrap(), Object::boolean(true));
+    }
+
+    #[test]
+    fn rust_equality_is_structural() {
+        assert_ne!(Object::float(1.0), Object::integer(1));
+        assert_ne!(Object::list(vec![Object::integer(1)]), Object::list(vec![Object::float(1.0)]));
+    }
+
+    #[test]
+    fn integers_never_equal_strings() {
+        assert_eq!(run("1 == \"1\"").unwrap(), Object::boolean(false));
+        assert_eq!(run("1 != \"1\"").unwrap(), Object::boolean(true));
+    }
+
+    #[test]
+    fn lists_are_equal_element_wise() {
+        assert_eq!(run("[1, [2, 3]] == [1.0, [2, 3]]").unwrap(), Object::boolean(true));
+        assert_eq!(run("[1, 2] == [2, 1]").unwrap(), Object::boolean(false));
+        assert_eq!(run("[1, 2] == [1, 2, 3]").unwrap(), Object::boolean(false));
+    }
+
+    #[test]
+    fn zero_divided_by_zero_is_nan() {
+        let result = run("0.0 / 0.0").unwrap();
+        assert!(result.as_f32().is_some_and(f32::is_nan));
+        assert_eq!(run("x = 0.0 / 0.0; x != x").unwrap(), Object::boolean(true));
+        assert_eq!(run("x = 0.0 / 0.0; x == x").unwrap(), Object::boolean(false));
+    }
+
+    #[test]
+    fn infinities_compare() {
+        assert_eq!(run("1.0 / 0.0 > 1000000.0").unwrap(), Object::boolean(true));
+        assert_eq!(run("-1.0 / 0.0 < -1000000.0").unwrap(), Object::boolean(true));
+    }
+
+    #[test]
+    fn non_finite_floats_print_short_names() {
+        assert_eq!(run("1.0 / 0.0").unwrap().to_string(), "inf");
+        assert_eq!(run("-1.0 / 0.0").unwrap().to_string(), "-inf");
+        assert_eq!(run("0.0 / 0.0").unwrap().to_string(), "nan");
+        assert_eq!(format!("{:?}", Object::float(f32::NAN)), "Float(nan)");
+    }
+
+    #[test]
+    fn calling_a_non_function_errors() {
+        let error = run("x = 5;\nx(1)").unwrap_err();
+
+        match error.downcast_ref() {
+            Some(EvaluatorError::NotCallable { found, pos }) => {
+                assert_eq!(*found, ObjectType::Integer);
+                assert_eq!(pos.line, 2);
+            },
+            _ => panic!("expected a not callable error, got {}", error),
+        }
+    }
+
+    fn argument_count(result: EvaluatorResult<Object>) -> (usize, Option<usize>, usize) {
+        match result.unwrap_err().downcast_ref() {
+            Some(EvaluatorError::ArgumentCount { min, max, found, .. }) => (*min, *max, *found),
+            _ => panic!("expected an argument count error"),
+        }
+    }
+
+    #[test]
+    fn too_few_arguments_error() {
+        assert_eq!(argument_count(run("add = (a, b) { a + b; }; add(1)")), (2, Some(2), 1));
+    }
+
+    #[test]
+    fn too_many_arguments_error() {
+        assert_eq!(argument_count(run("add = (a, b) { a + b; }; add(1, 2, 3)")), (2, Some(2), 3));
+    }
+
+    #[test]
+    fn default_parameter_is_used_when_omitted() {
+        let program = "greet = (name, greeting = \"Hello\") { greeting + \", \" + name; };\n";
+        assert_eq!(run(&(program.to_string() + "greet(\"World\")")).unwrap(), Object::string("Hello, World"));
+        assert_eq!(run(&(program.to_string() + "greet(\"World\", \"Hi\")")).unwrap(), Object::string("Hi, World"));
+    }
+
+    #[test]
+    fn rest_parameter_collects_trailing_arguments() {
+        let program = "collect = (first, ...others) { others; };\n";
+        let collect = |call: &str| run(&(program.to_string() + call)).unwrap();
+
+        assert_eq!(collect("collect(1)"), Object::list(vec![]));
+        assert_eq!(collect("collect(1, 2)"), Object::list(vec![Object::integer(2)]));
+        assert_eq!(collect("collect(1, 2, 3, 4)"), Object::list(vec![Object::integer(2), Object::integer(3), Object::integer(4)]));
+    }
+
+    #[test]
+    fn pipe_applies_each_stage() {
+        let program = "double = (x) { x * 2; };\ninc = (x) { x + 1; };\n3 |> double |> inc";
+        assert_eq!(run(program).unwrap(), Object::integer(7));
+    }
+
+    #[test]
+    fn pipe_into_non_function_errors() {
+        let error = run("y = 2;\n3 |> y").unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(EvaluatorError::NotCallable { found: ObjectType::Integer, .. })), "{}", error);
+    }
+
+    #[test]
+    fn float_modulo() {
+        assert_eq!(run("5.5 % 2.0").unwrap(), Object::float(1.5));
+        assert_eq!(run("5.5 % 2").unwrap(), Object::float(1.5));
+        assert_eq!(run("7 % 2.5").unwrap(), Object::float(2.0));
+    }
+
+    #[test]
+    fn modulo_by_zero() {
+        let error = run("5 % 0").unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(object::Error::DivisionByZero)), "{}", error);
+        assert!(run("5.5 % 0.0").unwrap().as_f32().is_some_and(f32::is_nan));
+    }
+
+    #[test]
+    fn nested_lists_display_recursively() {
+        assert_eq!(run("[1, [2, 3], \"x\"]").unwrap().to_string(), "[1, [2, 3], \"x\"]");
+    }
+
+    #[test]
+    fn exit_stops_evaluation_with_its_code() {
+        let error = run("x = 1;\nexit(2);\nx = undefined_name").unwrap_err();
+        assert_eq!(crate::errors::exit_code(&error), Some(2));
+
+        let error = run("exit()").unwrap_err();
+        assert_eq!(crate::errors::exit_code(&error), Some(0));
+    }
+
+    #[test]
+    fn trailing_commas_are_allowed() {
+        assert_eq!(run("add = (a, b,) { a + b; }; add(1, 2,)").unwrap(), Object::integer(3));
+        assert_eq!(run("[1, 2,]").unwrap(), Object::list(vec![Object::integer(1), Object::integer(2)]));
+    }
+
+    #[test]
+    fn assigning_to_discard_evaluates_the_value() {
+        assert_eq!(run("calls = 0;\ncount = () { calls = calls + 1; };\n_ = count();\ncalls").unwrap(), Object::integer(1));
+    }
+
+    #[test]
+    fn match_picks_the_matching_arm() {
+        let program = |n: i32| format!("x = match {} {{\n    1 => \"one\",\n    2 => \"two\",\n    _ => \"many\",\n}}\nx", n);
+
+        assert_eq!(run(&program(2)).unwrap(), Object::string("two"));
+        assert_eq!(run(&program(7)).unwrap(), Object::string("many"));
+    }
+
+    #[test]
+    fn match_without_matching_arm_errors() {
+        let error = run("x = match 3 { 1 => \"one\", 2 => \"two\" }").unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(EvaluatorError::NoMatch { value, .. }) if value == "3"), "{}", error);
+    }
+
+    /// The expression of a program made of a single expression statement
+    fn expression(input: &str) -> Expression {
+        match statements(input).remove(0) {
+            Node::ExpressionStatement(ExpressionStatement(expression, _)) => expression,
+            node => panic!("expected an expression, got {:?}", node),
+        }
+    }
+
+    #[test]
+    fn eval_expression_uses_the_current_scope() {
+        let program = statements("x = 41;");
+        let mut evaluator = Evaluator::new(&program);
+        evaluator.eval().unwrap();
+
+        assert_eq!(evaluator.eval_expression(&expression("x + 1;")).unwrap(), Object::integer(42));
+    }
+
+    fn run_overflowing(integer_overflow: IntegerOverflow, input: &str) -> EvaluatorResult<Object> {
+        run_with(EvaluatorOptions { integer_overflow, ..Default::default() }, input)
+    }
+
+    #[test]
+    fn checked_overflow_errors() {
+        let error = run_overflowing(IntegerOverflow::Checked, "2147483647 + 1").unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(object::Error::IntegerOverflow)), "{}", error);
+    }
+
+    #[test]
+    fn wrapping_overflow_wraps() {
+        assert_eq!(run_overflowing(IntegerOverflow::Wrapping, "2147483647 + 1").unwrap(), Object::integer(i32::MIN));
+        assert_eq!(run_overflowing(IntegerOverflow::Wrapping, "2147483647 * 2").unwrap(), Object::integer(-2));
+    }
+
+    #[test]
+    fn saturating_overflow_clamps() {
+        assert_eq!(run_overflowing(IntegerOverflow::Saturating, "2147483647 + 1").unwrap(), Object::integer(i32::MAX));
+        assert_eq!(run_overflowing(IntegerOverflow::Saturating, "-2147483647 - 10").unwrap(), Object::integer(i32::MIN));
+    }
+
+    #[test]
+    fn arguments_may_span_commented_lines() {
+        let program = "add = (a, b) { a + b; };\nadd(\n    1, # first\n\n    2 # second\n)";
+        assert_eq!(run(program).unwrap(), Object::integer(3));
+    }
+
+    #[test]
+    fn assert_eq_passes_for_equal_values() {
+        assert_eq!(run("assert_eq([1, 2], [1.0, 2])").unwrap(), Object::void());
+    }
+
+    #[test]
+    fn assert_eq_shows_both_sides() {
+        let error = run("assert_eq(1 + 1, \"three\")").unwrap_err();
+        let message = error.to_string();
+
+        assert!(message.contains('2') && message.contains("three"), "{}", message);
+    }
+
+    #[test]
+    fn reading_undefined_variable_errors() {
+        let error = run("x = 1;\ny = x + missing").unwrap_err();
+
+        match error.downcast_ref() {
+            Some(EvaluatorError::ObjectNotFound { name, pos }) => {
+                assert_eq!(name, "missing");
+                assert_eq!(pos.to_tuple(), (2, 9));
+            },
+            _ => panic!("expected an object not found error, got {}", error),
+        }
+    }
+
+    #[test]
+    fn index_after_call() {
+        assert_eq!(run("f = () { [10, 20]; };\nf()[1]").unwrap(), Object::integer(20));
+    }
+
+    #[test]
+    fn call_after_index() {
+        assert_eq!(run("double = (x) { x * 2; };\nfunctions = [double];\nfunctions[0](4)").unwrap(), Object::integer(8));
+    }
+
+    #[test]
+    fn in_checks_list_membership() {
+        assert_eq!(run("2 in [1, 2, 3]").unwrap(), Object::boolean(true));
+        assert_eq!(run("4 in [1, 2, 3]").unwrap(), Object::boolean(false));
+    }
+
+    #[test]
+    fn in_checks_substrings() {
+        assert_eq!(run("\"ab\" in \"abc\"").unwrap(), Object::boolean(true));
+        assert_eq!(run("\"ba\" in \"abc\"").unwrap(), Object::boolean(false));
+    }
+
+    #[test]
+    fn in_rejects_other_types() {
+        let error = run("1 in 5").unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(EvaluatorError::OperandTypes { .. })), "{}", error);
+    }
+
+    fn integers(values: &[i32]) -> Object {
+        Object::list(values.iter().copied().map(Object::integer).collect())
+    }
+
+    #[test]
+    fn slice_takes_a_range() {
+        assert_eq!(run("slice([1, 2, 3, 4], 1, 3)").unwrap(), integers(&[2, 3]));
+        assert_eq!(run("slice(\"hello\", 1, 3)").unwrap(), Object::string("el"));
+        assert_eq!(run("slice([1, 2, 3, 4], 2)").unwrap(), integers(&[3, 4]));
+    }
+
+    #[test]
+    fn slice_clamps_out_of_range_bounds() {
+        assert_eq!(run("slice([1, 2, 3], 1, 100)").unwrap(), integers(&[2, 3]));
+        assert_eq!(run("slice([1, 2, 3], 5, 10)").unwrap(), integers(&[]));
+        assert_eq!(run("slice([1, 2, 3], 2, 1)").unwrap(), integers(&[]));
+    }
+
+    #[test]
+    fn slice_counts_negative_bounds_from_the_end() {
+        assert_eq!(run("slice([1, 2, 3, 4], -2)").unwrap(), integers(&[3, 4]));
+        assert_eq!(run("slice(\"hello\", -4, -1)").unwrap(), Object::string("ell"));
+        assert_eq!(run("slice([1, 2], -10, 1)").unwrap(), integers(&[1]));
+    }
+
+    #[test]
+    fn contains_and_index_of_find_present_values() {
+        assert_eq!(run("contains(\"hello\", \"ll\")").unwrap(), Object::boolean(true));
+        assert_eq!(run("contains([1, [2]], [2])").unwrap(), Object::boolean(true));
+        assert_eq!(run("index_of(\"hello\", \"ll\")").unwrap(), Object::integer(2));
+        assert_eq!(run("index_of([1, 2, 3], 3.0)").unwrap(), Object::integer(2));
+    }
+
+    #[test]
+    fn contains_and_index_of_miss_absent_values() {
+        assert_eq!(run("contains(\"hello\", \"z\")").unwrap(), Object::boolean(false));
+        assert_eq!(run("contains([1, 2], 3)").unwrap(), Object::boolean(false));
+        assert_eq!(run("index_of(\"hello\", \"z\")").unwrap(), Object::integer(-1));
+        assert_eq!(run("index_of([1, 2], 3)").unwrap(), Object::integer(-1));
+    }
+
+    #[test]
+    fn contains_rejects_other_types() {
+        let error = run("contains(5, 1)").unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(EvaluatorError::InvalidType { .. })), "{}", error);
+    }
+
+    #[test]
+    fn reset_keeps_only_builtins() {
+        let program = statements("x = 1;");
+        let mut evaluator = Evaluator::new(&program);
+        evaluator.eval().unwrap();
+        evaluator.reset();
+
+        let error = evaluator.eval_expression(&expression("x;")).unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(EvaluatorError::ObjectNotFound { name, .. }) if name == "x"), "{}", error);
+
+        assert_eq!(evaluator.eval_expression(&expression("typeof(1);")).unwrap(), Object::string("integer"));
+    }
+
+    #[test]
+    fn results_convert_to_rust_values() {
+        assert_eq!(run("40 + 2").unwrap().as_i64(), Some(42));
+        assert_eq!(run("1.5 * 2").unwrap().as_f64(), Some(3.0));
+        assert_eq!(run("1 < 2").unwrap().as_boolean(), Some(true));
+        assert_eq!(run("\"a\" + \"b\"").unwrap().as_str(), Some("ab"));
+        assert_eq!(run("[1, 2]").unwrap().as_list().map(Vec::len), Some(2));
+
+        let result = run("\"not a number\"").unwrap();
+        assert_eq!(result.as_i64(), None);
+        assert_eq!(result.as_list(), None);
+    }
+
+    #[test]
+    fn seeded_globals_are_readable_from_scripts() {
+        let program = statements("total = 0; for i = 0; i < 3; i += 1 { total += numbers[i]; }; name + \": \" + (total + offset);");
+        let mut evaluator = Evaluator::new(&program);
+
+        evaluator.define_global("numbers", Object::from(vec![1, 2, 3]));
+        evaluator.define_global("offset", Object::from(0.5));
+        evaluator.define_global("name", Object::from("yaipl"));
+
+        assert_eq!(evaluator.eval().unwrap(), Object::string("yaipl: 6.5"));
+    }
+
+    #[test]
+    fn wide_rust_numbers_convert_when_they_fit() {
+        assert_eq!(Object::try_from(42_i64).unwrap(), Object::integer(42));
+        assert!(Object::try_from(i64::from(i32::MAX) + 1).is_err());
+        assert_eq!(Object::from(0.25_f64), Object::float(0.25));
+    }
+
+    #[test]
+    fn registered_functions_are_callable() {
+        let program = statements("sum(1, 2, 3) * 2;");
+        let mut evaluator = Evaluator::new(&program);
+
+        evaluator.register("sum", |args| {
+            Ok(Object::integer(args.iter().filter_map(Object::as_integer).sum()))
+        });
+
+        assert_eq!(evaluator.eval().unwrap(), Object::integer(12));
+    }
+
+    #[test]
+    fn step_hook_runs_before_each_statement() {
+        let program = statements("x = 1;\ny = 2;\nx + y");
+        let mut evaluator = Evaluator::new(&program);
+
+        let lines = Rc::new(std::cell::RefCell::new(Vec::new()));
+        let seen = Rc::clone(&lines);
+        evaluator.on_step(move |pos| {
+            seen.borrow_mut().push(pos.line);
+            StepSignal::Continue
+        });
+
+        assert_eq!(evaluator.eval().unwrap(), Object::integer(3));
+        assert_eq!(*lines.borrow(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn step_hook_can_stop_evaluation() {
+        let program = statements("x = 1;\ny = 2");
+        let mut evaluator = Evaluator::new(&program);
+        evaluator.on_step(|pos| if pos.line == 2 { StepSignal::Stop } else { StepSignal::Continue });
+
+        let error = evaluator.eval().unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(EvaluatorError::Stopped { pos }) if pos.line == 2), "{}", error);
+    }
+
+    #[test]
+    fn methods_dispatch_on_the_receiver_type() {
+        assert_eq!(run("(-5).abs()").unwrap(), Object::integer(5));
+        assert_eq!(run("(3.7).floor()").unwrap(), Object::float(3.0));
+        assert_eq!(run("\"hi\".upper()").unwrap(), Object::string("HI"));
+    }
+
+    #[test]
+    fn unknown_method_errors() {
+        let error = run("x = 5;\nx.upper()").unwrap_err();
+
+        match error.downcast_ref() {
+            Some(EvaluatorError::UnknownMethod { found, name, pos }) => {
+                assert_eq!((found, name.as_str(), pos.line), (&ObjectType::Integer, "upper", 2));
+            },
+            _ => panic!("expected an unknown method error, got {}", error),
+        }
+    }
+
+    #[test]
+    fn join_puts_the_separator_between_strings() {
+        assert_eq!(run("join([\"a\", \"b\", \"c\"], \", \")").unwrap(), Object::string("a, b, c"));
+        assert_eq!(run("join([\"a\", \"b\"])").unwrap(), Object::string("ab"));
+    }
+
+    #[test]
+    fn join_of_an_empty_list_is_empty() {
+        assert_eq!(run("join([], \"-\")").unwrap(), Object::string(""));
+    }
+
+    #[test]
+    fn join_names_the_first_non_string_element() {
+        let error = run("join([\"a\", 2, 3], \"-\")").unwrap_err();
+
+        match error.downcast_ref() {
+            Some(EvaluatorError::InvalidElementType { index, found, .. }) => {
+                assert_eq!((*index, found), (1, &ObjectType::Integer));
+            },
+            _ => panic!("expected an invalid element type error, got {}", error),
+        }
+    }
+
+    #[test]
+    fn range_counts_up_to_the_exclusive_end() {
+        assert_eq!(run("range(0, 4)").unwrap(), integers(&[0, 1, 2, 3]));
+        assert_eq!(run("range(1, 8, 3)").unwrap(), integers(&[1, 4, 7]));
+    }
+
+    #[test]
+    fn range_counts_down_with_a_negative_step() {
+        assert_eq!(run("range(5, 0, -2)").unwrap(), integers(&[5, 3, 1]));
+    }
+
+    #[test]
+    fn range_rejects_a_zero_step() {
+        let error = run("range(0, 5, 0)").unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(EvaluatorError::ZeroStep)), "{}", error);
+    }
+
+    #[test]
+    fn range_with_one_argument_starts_at_zero() {
+        assert_eq!(run("range(3)").unwrap(), integers(&[0, 1, 2]));
+        assert_eq!(run("range(-3)").unwrap(), integers(&[]));
+    }
+
+    #[test]
+    fn range_elements_count_against_the_iteration_limit() {
+        let error = run_with(limited(100), "range(0, 2147483647)").unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(EvaluatorError::IterationLimit { limit: 100, .. })), "{}", error);
+
+        assert!(run_with(limited(10), "range(5)\nfor i = 0; i < 5; i += 1 { }").is_ok());
+        assert!(run_with(limited(10), "range(6)\nfor i = 0; i < 5; i += 1 { }").is_err());
+    }
+
+    #[test]
+    fn dbg_returns_its_argument() {
+        assert_eq!(run("dbg(2 + 3) * 2").unwrap(), Object::integer(10));
+        assert_eq!(run("dbg(\"hi\")").unwrap(), Object::string("hi"));
+    }
+
+    #[test]
+    fn dbg_prints_the_position_and_debug_form() {
+        let line = yaipl_std::debug_line(&Position::from(3, 5), &Object::integer(5));
+        assert_eq!(line, "[3:5] Integer(5)");
+    }
+
+    #[test]
+    fn first_last_and_rest_split_a_list() {
+        assert_eq!(run("first([1, 2, 3])").unwrap(), Object::integer(1));
+        assert_eq!(run("last([1, 2, 3])").unwrap(), Object::integer(3));
+        assert_eq!(run("rest([1, 2, 3])").unwrap(), integers(&[2, 3]));
+    }
+
+    #[test]
+    fn first_and_last_of_an_empty_list_are_null() {
+        assert_eq!(run("first([])").unwrap(), Object::null());
+        assert_eq!(run("last([])").unwrap(), Object::null());
+        assert_eq!(run("rest([])").unwrap(), integers(&[]));
+    }
+
+    #[test]
+    fn first_rejects_other_types() {
+        let error = run("first(\"abc\")").unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(EvaluatorError::InvalidType { .. })), "{}", error);
+    }
+
+    #[test]
+    fn negative_literals_evaluate_to_their_value() {
+        assert_eq!(run("-5").unwrap(), Object::integer(-5));
+        assert_eq!(run("-3.14").unwrap().to_string(), "-3.14");
+        assert_eq!(run("-2147483648").unwrap(), Object::integer(i32::MIN));
+        assert_eq!(run("match -2147483648 { -2147483648 => true, _ => false }").unwrap(), Object::boolean(true));
+    }
+
+    #[test]
+    fn trailing_empty_statements_keep_the_result() {
+        let mut program = statements("1 + 1");
+        program.push(Node::EmptyStatement(EmptyStatement()));
+        program.push(Node::EmptyStatement(EmptyStatement()));
+
+        assert_eq!(Evaluator::new(&program).eval().unwrap(), Object::integer(2));
+        assert_eq!(run("1 + 1\n\n\n").unwrap(), Object::integer(2));
+    }
+
+    #[test]
+    fn assigned_lists_are_copies() {
+        assert_eq!(run("a = [1, 2, 3]\nb = a\nb = rest(b)\na").unwrap(), integers(&[1, 2, 3]));
+    }
+
+    #[test]
+    fn lists_passed_to_functions_are_copies() {
+        let input = "a = [1, 2]\nf = (list) { list = [9]; list; }\nb = f(a)\n[a, b]";
+        assert_eq!(run(input).unwrap(), Object::list(vec![integers(&[1, 2]), integers(&[9])]));
+    }
+
+    fn limited(max_iterations: usize) -> EvaluatorOptions {
+        EvaluatorOptions { max_iterations: Some(max_iterations), ..EvaluatorOptions::default() }
+    }
+
+    #[test]
+    fn iteration_limit_stops_an_infinite_loop() {
+        let error = run_with(limited(100), "i = 0\nwhile true { i += 1; }").unwrap_err();
+
+        match error.downcast_ref() {
+            Some(EvaluatorError::IterationLimit { limit, pos }) => assert_eq!((*limit, pos.line), (100, 2)),
+            _ => panic!("expected an iteration limit error, got {}", error),
+        }
+    }
+
+    #[test]
+    fn iteration_limit_counts_across_loops() {
+        let loops = "for i = 0; i < 50; i += 1 { }\nfor i = 0; i < 50; i += 1 { }\n";
+        assert!(run_with(limited(100), loops).is_ok());
+
+        let error = run_with(limited(100), &format!("{}i = 0\nwhile i < 1 {{ i += 1; }}", loops)).unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(EvaluatorError::IterationLimit { .. })), "{}", error);
+    }
+
+    fn call_depth(limit: usize) -> EvaluatorOptions {
+        EvaluatorOptions { max_call_depth: limit, ..EvaluatorOptions::default() }
+    }
+
+    #[test]
+    fn unbounded_recursion_hits_the_call_depth_limit() {
+        let error = run_with(call_depth(50), "f = () { f(); }\nf()").unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(EvaluatorError::CallDepthLimit { limit: 50, pos }) if pos.line == 1), "{}", error);
+    }
+
+    #[test]
+    fn recursion_within_the_call_depth_limit_runs() {
+        let count = "count = (n) { if n == 0 { 0; } else { count(n - 1) + 1; } }\n";
+
+        assert_eq!(run_with(call_depth(50), &format!("{}count(49)", count)).unwrap(), Object::integer(49));
+        assert!(run_with(call_depth(50), &format!("{}count(50)", count)).is_err());
+    }
+
+    #[test]
+    fn timeout_aborts_a_long_loop() {
+        let options = EvaluatorOptions { timeout: Some(Duration::from_millis(50)), ..EvaluatorOptions::default() };
+
+        let start = std::time::Instant::now();
+        let error = run_with(options, "i = 0\nwhile true { i += 1; }").unwrap_err();
+
+        assert!(matches!(error.downcast_ref(), Some(EvaluatorError::Timeout { .. })), "{}", error);
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn short_programs_finish_within_the_timeout() {
+        let options = EvaluatorOptions { timeout: Some(Duration::from_secs(10)), ..EvaluatorOptions::default() };
+        assert_eq!(run_with(options, "1 + 1").unwrap(), Object::integer(2));
+    }
+
+    #[test]
+    fn print_arguments_are_joined_with_spaces() {
+        let args = [Object::string("a"), Object::integer(1), Object::float(2.5), integers(&[3])];
+        assert_eq!(yaipl_std::join_arguments(&args), "a 1 2.5 [3]");
+        assert_eq!(yaipl_std::join_arguments(&[]), "");
+    }
+
+    #[test]
+    fn print_and_println_return_nothing() {
+        assert_eq!(run("print(\"a\", 1)").unwrap(), Object::void());
+        assert_eq!(run("println()").unwrap(), Object::void());
+    }
+
+    #[test]
+    fn operand_type_error_points_at_the_string_operand() {
+        let error = run("x = 1\ny = x - \"a\"").unwrap_err();
+
+        match error.downcast_ref() {
+            Some(EvaluatorError::OperandTypes { left, right, pos, .. }) => {
+                assert_eq!((&left.0, left.1.to_tuple()), (&ObjectType::Integer, (2, 5)));
+                assert_eq!((&right.0, right.1.to_tuple()), (&ObjectType::String, (2, 9)));
+                assert_eq!(pos.to_tuple(), (2, 9));
+            },
+            _ => panic!("expected an operand types error, got {}", error),
+        }
+    }
+
+    #[test]
+    fn compound_assignment_type_error_points_at_the_string() {
+        let error = run("x = \"a\"\nx -= 1").unwrap_err();
+        assert_eq!(crate::errors::error_position(&error).map(|pos| pos.to_tuple()), Some((2, 1)), "{}", error);
+    }
+
+    #[test]
+    fn from_ref_evaluates_a_borrowed_program() {
+        let program = parse("x = 20; x + 22").unwrap();
+
+        // The program is only borrowed, so it can be evaluated again afterwards
+        assert_eq!(Evaluator::from_ref(&program).unwrap().eval().unwrap(), Object::integer(42));
+        assert_eq!(Evaluator::from_ref(&program).unwrap().eval().unwrap(), Object::integer(42));
+    }
+
+    #[test]
+    fn from_ref_rejects_other_nodes() {
+        let statement = Node::EmptyStatement(EmptyStatement());
+        assert!(Evaluator::from_ref(&statement).is_err());
+    }
+
+    #[test]
+    fn or_returns_the_deciding_operand() {
+        assert_eq!(run("0 || \"default\"").unwrap(), Object::string("default"));
+        assert_eq!(run("\"set\" || \"default\"").unwrap(), Object::string("set"));
+    }
+
+    #[test]
+    fn and_returns_the_deciding_operand() {
+        assert_eq!(run("\"x\" && 5").unwrap(), Object::integer(5));
+        assert_eq!(run("\"\" && 5").unwrap(), Object::string(""));
+    }
+
+    #[test]
+    fn logical_operators_short_circuit() {
+        assert_eq!(run("0 && missing()").unwrap(), Object::integer(0));
+        assert_eq!(run("1 || missing()").unwrap(), Object::integer(1));
+    }
+
+    #[test]
+    fn comparisons_still_return_booleans() {
+        assert_eq!(run("(1 < 2) && (2 < 3)").unwrap(), Object::boolean(true));
+    }
+
+    #[test]
+    fn not_negates_truthiness_by_default() {
+        assert_eq!(run("!0").unwrap(), Object::boolean(true));
+        assert_eq!(run("!\"\"").unwrap(), Object::boolean(true));
+        assert_eq!(run("!5").unwrap(), Object::boolean(false));
+    }
+
+    #[test]
+    fn strict_not_requires_a_boolean() {
+        let strict = EvaluatorOptions { strict_not: true, ..EvaluatorOptions::default() };
+
+        assert_eq!(run_with(strict.clone(), "!false").unwrap(), Object::boolean(true));
+
+        let error = run_with(strict, "!5").unwrap_err();
+        match error.downcast_ref() {
+            Some(EvaluatorError::InvalidType { found, .. }) => assert_eq!(found, &ObjectType::Integer),
+            _ => panic!("expected an invalid type error, got {}", error),
+        }
+    }
+
+    #[test]
+    fn length_property_of_strings_and_lists() {
+        assert_eq!(run("\"abc\".length").unwrap(), Object::integer(3));
+        assert_eq!(run("x = [1, 2, 3, 4]\nx.length").unwrap(), Object::integer(4));
+        assert_eq!(run("[].length").unwrap(), Object::integer(0));
+    }
+
+    #[test]
+    fn length_of_other_types_errors() {
+        let error = run("x = 5\nx.length").unwrap_err();
+
+        match error.downcast_ref() {
+            Some(EvaluatorError::UnknownProperty { found, name, pos }) => {
+                assert_eq!((found, name.as_str(), pos.line), (&ObjectType::Integer, "length", 2));
+            },
+            _ => panic!("expected an unknown property error, got {}", error),
+        }
+    }
+
+    #[test]
+    fn boxed_errors_keep_their_original_content() {
+        let error = run("x = 1;\ny = 2 3").unwrap_err();
+
+        match error.downcast_ref() {
+            Some(ParserError::InvalidToken { pos, .. }) => {
+                assert_eq!(pos.line, 2);
+                assert!(error.to_string().contains(":2:"), "{}", error);
+            },
+            _ => panic!("expected an invalid token error, got {}", error),
+        }
     }
 }
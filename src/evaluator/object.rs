@@ -1,22 +1,27 @@
-use std::{alloc::{alloc, dealloc, Layout}, cmp::Ordering, fmt::Display, ptr::{addr_of_mut, drop_in_place}};
+use std::{cmp::Ordering, fmt::Display, num::TryFromIntError, rc::Rc};
 
-use crate::parser::ast::{Expression, FunctionDeclareExpression};
+use crate::{lexer::token::Position, parser::ast::{ArithmeticOperator, FunctionDeclareExpression}};
 
-use super::environment::Environment;
-
-// largely based on https://github.com/dannyvankooten/nederlang/blob/tree-walker/src/object.rs
+use super::{environment::Environment, EvaluatorResult, IntegerOverflow};
 
 pub const FUNCTION_PREFIX: &str = "__fc_";
+/// Name which can be assigned to but is never stored or read, e.g. `_ = f();`
+pub const DISCARD: &str = "_";
 
-#[derive(Clone, Debug)]
-pub struct Object(*mut u8);
-
-const TAG_MASK: usize = 0b111;
-const PTR_MASK: usize = !TAG_MASK;
-const VALUE_SHIFT_BITS: usize = 3;
-
-// TODO: Fix this. Tagged pointers cannot have more than 8 bits which is not enough for the amount of types we have
-// Possibly remove NativeFunction 
+#[derive(Clone)]
+pub enum Object {
+    Null,
+    Integer(i32),
+    Boolean(bool),
+    Float(f32),
+    String(String),
+    /// Lists have value semantics. Assigning, passing or returning one copies it, so bindings
+    /// never share elements and no list operation may change a list in place
+    List(Vec<Object>),
+    Function(Rc<FunctionDeclareExpression>),
+    NativeFunction(Rc<NativeFunctionObject>),
+    Void,
+}
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum ObjectType {
@@ -47,56 +52,66 @@ impl Display for ObjectType {
     }
 }
 
-#[derive(Clone, Debug)]
-pub struct NativeFunctionObject<'a>(pub &'a str, pub Vec<String>, pub fn(&mut Environment, Vec<Object>) -> Object);
+/// Called with the environment, the arguments and the position of the call
+pub type NativeFunction = Rc<dyn Fn(&mut Environment, Vec<Object>, &Position) -> EvaluatorResult<Object>>;
+
+#[derive(Clone)]
+pub struct NativeFunctionObject(pub String, pub Vec<String>, pub NativeFunction);
 
-impl<'a> Object {
-    fn from_type(pointer: *mut u8, object_type: ObjectType) -> Self {
-        Self((pointer as usize | object_type as usize & TAG_MASK) as _)
+impl std::fmt::Debug for NativeFunctionObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("NativeFunctionObject").field(&self.0).field(&self.1).finish()
     }
+}
 
+impl Object {
     pub fn null() -> Self {
-        Self::from_type(0 as _, ObjectType::Null)
+        Object::Null
     }
 
     pub fn void() -> Self {
-        Self::from_type(0 as _, ObjectType::Void)
+        Object::Void
     }
 
     pub fn integer(value: i32) -> Self {
-        Self::from_type((value << VALUE_SHIFT_BITS) as _, ObjectType::Integer)
+        Object::Integer(value)
     }
     
     pub fn boolean(value: bool) -> Self {
-        match value {
-            false => Self::from_type(0 as _, ObjectType::Boolean),
-            true => Self::from_type((1 << VALUE_SHIFT_BITS) as _, ObjectType::Boolean)
-        }
+        Object::Boolean(value)
     }
 
-    pub fn list(list: &'a Vec<Expression>) -> Self {
-        Self::from_type(list as *const Vec<Expression> as _, ObjectType::List)
+    pub fn list(list: Vec<Object>) -> Self {
+        Object::List(list)
     }
     
-    pub fn function(func: &'a FunctionDeclareExpression) -> Self {
-        Self::from_type(func as *const FunctionDeclareExpression as _, ObjectType::Function)
+    pub fn function(func: &FunctionDeclareExpression) -> Self {
+        Object::Function(Rc::new(func.to_owned()))
     }
 
-    pub fn native_function(func: &'a NativeFunctionObject) -> Self {
-        Self::from_type(func as *const NativeFunctionObject as _, ObjectType::NativeFunction)
+    pub fn native_function(func: NativeFunctionObject) -> Self {
+        Object::NativeFunction(Rc::new(func))
     }
 
-    pub fn string(value: &'a str) -> Self {
-        YaiplString::from_str(value)
+    pub fn string(value: &str) -> Self {
+        Object::String(value.to_string())
     }
 
     pub fn float(value: f32) -> Self {
-        YaiplFloat::from_f32(value)
+        Object::Float(value)
     }
 
     pub fn get_type(&self) -> ObjectType {
-        unsafe {
-            std::mem::transmute((self.0 as usize & TAG_MASK) as u8)
+        match self {
+            Object::Null => ObjectType::Null,
+            Object::Integer(_) => ObjectType::Integer,
+            Object::Boolean(_) => ObjectType::Boolean,
+            Object::Float(_) => ObjectType::Float,
+            Object::String(_) => ObjectType::String,
+            Object::List(_) => ObjectType::List,
+            Object::Function(_) => ObjectType::Function,
+            Object::NativeFunction(_) => ObjectType::NativeFunction,
+            Object::Void => ObjectType::Void,
         }
     }
 
@@ -105,73 +120,101 @@ impl<'a> Object {
     }
     
     pub fn as_boolean(&self) -> Option<bool> {
-        match self.get_type() {
-            ObjectType::Boolean => Some((self.0 as u8 >> VALUE_SHIFT_BITS) as u8 != 0),
+        match self {
+            Object::Boolean(value) => Some(*value),
             _ => None
         }
     }
 
     pub fn as_integer(&self) -> Option<i32> {
-        match self.get_type() {
-            ObjectType::Integer => Some(self.0 as i32 >> VALUE_SHIFT_BITS),
+        match self {
+            Object::Integer(value) => Some(*value),
             _ => None
         }
     }
 
-    pub fn as_list(&self) -> Option<&'a Vec<Expression>> {
-        match self.get_type() {
-            ObjectType::List => Some(unsafe { self.get::<Vec<Expression>>() }),
+    /// Widened integer value, for hosts that work with 64-bit numbers
+    pub fn as_i64(&self) -> Option<i64> {
+        self.as_integer().map(i64::from)
+    }
+
+    pub fn as_list(&self) -> Option<&Vec<Object>> {
+        match self {
+            Object::List(list) => Some(list),
             _ => None
         }
     }
 
-    pub fn as_function(&self) -> Option<&'a FunctionDeclareExpression> {
-        match self.get_type() {
-            ObjectType::Function => Some(unsafe { self.get::<FunctionDeclareExpression>() }),
+    pub fn as_function(&self) -> Option<&FunctionDeclareExpression> {
+        match self {
+            Object::Function(func) => Some(func),
             _ => None
         }
     }
 
-    pub fn as_native_function(&self) -> Option<&'a NativeFunctionObject> {
-        match self.get_type() {
-            ObjectType::NativeFunction => Some(unsafe { self.get::<NativeFunctionObject>() } ),
+    pub fn as_native_function(&self) -> Option<&NativeFunctionObject> {
+        match self {
+            Object::NativeFunction(func) => Some(func),
             _ => None
         }
     }
 
     pub fn as_f32(&self) -> Option<f32> {
-        match self.get_type() {
-            ObjectType::Float => Some(unsafe { let result: f32 = YaiplFloat::read(self).value; result }),
+        match self {
+            Object::Float(value) => Some(*value),
             _ => None
         }
     }
 
+    /// Widened float value, for hosts that work with 64-bit numbers
+    pub fn as_f64(&self) -> Option<f64> {
+        self.as_f32().map(f64::from)
+    }
+
     pub fn as_str(&self) -> Option<&str> {
-        match self.get_type() {
-            ObjectType::String => Some(unsafe { self.get::<YaiplString>().value.as_str() }),
+        match self {
+            Object::String(value) => Some(value.as_str()),
             _ => None
         }
     }
-    
-    pub fn as_ptr(&self) -> *mut u8 {
-        (self.0 as usize & PTR_MASK) as *mut u8
-    }
 
-    pub unsafe fn get<T>(&self) -> &'a T {
-        &*(self.as_ptr() as *const T)
+    /// Whether the value counts as true for conditions, `&&` and `||`. `false`, `null`, void, zero
+    /// and empty strings and lists are falsy, anything else is truthy
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Object::Null | Object::Void => false,
+            Object::Boolean(value) => *value,
+            Object::Integer(value) => *value != 0,
+            Object::Float(value) => *value != 0.0,
+            Object::String(value) => !value.is_empty(),
+            Object::List(elements) => !elements.is_empty(),
+            Object::Function(_) | Object::NativeFunction(_) => true,
+        }
     }
 
-    pub unsafe fn get_mut<T>(&self) -> &'a mut T {
-        &mut *(self.as_ptr() as *mut T)
+    /// Converts a boolean into an integer (`0` or `1`), leaving other types untouched
+    pub fn coerce_boolean(self) -> Self {
+        match self.as_boolean() {
+            Some(value) => Object::integer(value as i32),
+            None => self
+        }
     }
 
-    pub fn free(self) {
-        unsafe {
-            match self.get_type() {
-                ObjectType::Float => YaiplFloat::destroy(self),
-                ObjectType::String => YaiplString::destroy(self),
-                _ => {}
-            }
+    /// Equality used by `==` and `!=`. Integers and floats are compared numerically,
+    /// lists element-wise, and values of any other differing types are never equal.
+    pub fn equals(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Object::Integer(lhs), Object::Integer(rhs)) => lhs == rhs,
+            (Object::Float(lhs), Object::Float(rhs)) => lhs == rhs,
+            (Object::Integer(lhs), Object::Float(rhs)) => *lhs as f64 == *rhs as f64,
+            (Object::Float(lhs), Object::Integer(rhs)) => *lhs as f64 == *rhs as f64,
+            (Object::Boolean(lhs), Object::Boolean(rhs)) => lhs == rhs,
+            (Object::String(lhs), Object::String(rhs)) => lhs == rhs,
+            (Object::List(lhs), Object::List(rhs)) => lhs.len() == rhs.len() && lhs.iter().zip(rhs).all(|(lhs, rhs)| lhs.equals(rhs)),
+            (Object::Function(lhs), Object::Function(rhs)) => Rc::ptr_eq(lhs, rhs),
+            (Object::NativeFunction(lhs), Object::NativeFunction(rhs)) => lhs.0 == rhs.0,
+            (Object::Null, Object::Null) | (Object::Void, Object::Void) => true,
+            _ => false
         }
     }
 
@@ -179,13 +222,13 @@ impl<'a> Object {
         match self.get_type() {
             ObjectType::Integer => format!("integer({})", self.as_integer().expect("Couldn't take as integer")),
             ObjectType::Boolean => format!("boolean({})", self.as_boolean().expect("Couldn't take as boolean")),
-            ObjectType::Float => format!("float({})", self.as_f32().expect("Couldn't take as f32")),
+            ObjectType::Float => format!("float({})", format_float(self.as_f32().expect("Couldn't take as f32"))),
             ObjectType::String => format!("string(\"{}\")", self.as_str().expect("Couldn't take as str")),
             ObjectType::List => format!("list({})", self.as_list().expect("Couldn't take as list").len()),
-            ObjectType::Null => format!("null"),
-            ObjectType::Function => format!("function"),
-            ObjectType::NativeFunction => format!("nfunction"),
-            ObjectType::Void => format!("void")
+            ObjectType::Null => "null".to_string(),
+            ObjectType::Function => "function".to_string(),
+            ObjectType::NativeFunction => "nfunction".to_string(),
+            ObjectType::Void => "void".to_string()
         }
     }
 }
@@ -195,90 +238,117 @@ impl Display for Object {
         match self.get_type() {
             ObjectType::Boolean => write!(f, "{}", self.as_boolean().expect("Couldn't take as boolean")),
             ObjectType::Integer => write!(f, "{}", self.as_integer().expect("Couldn't take as integer")),
-            ObjectType::Float => write!(f, "{}", self.as_f32().expect("Couldn't take as f32")),
+            ObjectType::Float => write!(f, "{}", format_float(self.as_f32().expect("Couldn't take as f32"))),
             ObjectType::String => write!(f, "{}", self.as_str().expect("Couldn't take as str")),
-            ObjectType::List => write!(f, "[{}]", self.as_list().expect("Couldn't take as list").iter().map(|x| format!("{}", x)).collect::<Vec<String>>().join(", ")),
-            _ => write!(f, "{}", self.get_type().to_string())
+            ObjectType::List => write!(f, "[{}]", self.as_list().expect("Couldn't take as list").iter().map(format_element).collect::<Vec<String>>().join(", ")),
+            _ => write!(f, "{}", self.get_type())
         }
     }
 }
 
-#[repr(C)]
-pub struct Header {
-    pub marked: bool
+/// Formats a float, spelling out non-finite values as `inf`, `-inf` and `nan`
+fn format_float(value: f32) -> String {
+    if value.is_nan() {
+        String::from("nan")
+    } else if value.is_infinite() {
+        String::from(if value.is_sign_negative() { "-inf" } else { "inf" })
+    } else {
+        value.to_string()
+    }
 }
 
-impl Header {
-    pub unsafe fn read(obj: &Object) -> &mut Self {
-        obj.get_mut::<Self>()
+impl std::fmt::Debug for Object {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Object::Null => write!(f, "Null"),
+            Object::Integer(value) => f.debug_tuple("Integer").field(value).finish(),
+            Object::Boolean(value) => f.debug_tuple("Boolean").field(value).finish(),
+            // Spelled the same as when displayed, rather than Rust's `NaN`
+            Object::Float(value) => write!(f, "Float({})", format_float(*value)),
+            Object::String(value) => f.debug_tuple("String").field(value).finish(),
+            Object::List(elements) => f.debug_tuple("List").field(elements).finish(),
+            Object::Function(function) => f.debug_tuple("Function").field(function).finish(),
+            Object::NativeFunction(function) => f.debug_tuple("NativeFunction").field(function).finish(),
+            Object::Void => write!(f, "Void"),
+        }
     }
 }
 
-fn allocate(layout: Layout) -> *mut u8 {
-    unsafe { alloc(layout) }
+/// Formats an element of a list, quoting strings so `[1, "2"]` can be told apart from `[1, 2]`
+fn format_element(object: &Object) -> String {
+    match object {
+        Object::String(value) => format!("\"{}\"", value),
+        _ => object.to_string()
+    }
 }
 
-#[repr(C)]
-pub struct YaiplFloat {
-    header: Header,
-    value: f32,
+// Conversions from Rust values, e.g. for seeding globals with `Evaluator::define_global`
+impl From<i32> for Object {
+    fn from(value: i32) -> Self {
+        Object::integer(value)
+    }
 }
 
-impl YaiplFloat {
-    unsafe fn read(obj: &Object) -> &Self {
-        obj.get::<Self>()
-    }
+/// Integers are 32-bit in the language, so a wider one only converts if it fits
+impl TryFrom<i64> for Object {
+    type Error = TryFromIntError;
 
-    unsafe fn destroy(obj: Object) {
-        drop_in_place(obj.as_ptr() as *mut Self);
-        dealloc(obj.as_ptr(), Layout::new::<Self>());
+    fn try_from(value: i64) -> Result<Self, Self::Error> {
+        i32::try_from(value).map(Object::integer)
     }
+}
 
-    fn from_f32(value: f32) -> Object {
-        let ptr = Object::from_type(allocate(Layout::new::<Self>()), ObjectType::Float);
-        let obj = unsafe { ptr.get_mut::<Self>() };
-        obj.header.marked = false;
-        unsafe { addr_of_mut!(obj.value).write(value); }
+impl From<f32> for Object {
+    fn from(value: f32) -> Self {
+        Object::float(value)
+    }
+}
 
-        ptr
+/// Floats are 32-bit in the language, so this rounds to the nearest `f32` like `as` does
+impl From<f64> for Object {
+    fn from(value: f64) -> Self {
+        Object::float(value as f32)
     }
 }
 
-#[repr(C)]
-pub struct YaiplString {
-    header: Header,
-    value: String,
+impl From<bool> for Object {
+    fn from(value: bool) -> Self {
+        Object::boolean(value)
+    }
 }
 
-impl YaiplString {
-    unsafe fn destroy(obj: Object) {
-        drop_in_place(obj.as_ptr() as *mut Self);
-        dealloc(obj.as_ptr(), Layout::new::<Self>());
+impl From<&str> for Object {
+    fn from(value: &str) -> Self {
+        Object::string(value)
     }
+}
 
-    fn from_str(value: &str) -> Object {
-        let ptr = Object::from_type(allocate(Layout::new::<Self>()), ObjectType::String);
-        let obj = unsafe { ptr.get_mut::<Self>() };
-        obj.header.marked = false;
-        unsafe { addr_of_mut!(obj.value).write(value.to_string()); }
+impl From<String> for Object {
+    fn from(value: String) -> Self {
+        Object::String(value)
+    }
+}
 
-        ptr
+impl<T: Into<Object>> From<Vec<T>> for Object {
+    fn from(value: Vec<T>) -> Self {
+        Object::list(value.into_iter().map(Into::into).collect())
     }
 }
 
+/// Structural equality, so values of different types are never equal. The language's own `==` is
+/// [`Object::equals`]
 impl PartialEq for Object {
     fn eq(&self, other: &Self) -> bool {
-        if self.get_type() != other.get_type() {
-            return false;
-        }
-
-        match self.get_type() {
-            ObjectType::Boolean | ObjectType::Integer | ObjectType::Void 
-            | ObjectType::Function | ObjectType::NativeFunction | ObjectType::Null
-            | ObjectType::List => self.0 == other.0,
-
-            ObjectType::Float => self.as_f32().expect("Couldn't take as f32") == other.as_f32().expect("Couldn't take as f32"),
-            ObjectType::String => self.as_str().expect("Couldn't take as str") == other.as_str().expect("Couldn't take as str")
+        match (self, other) {
+            (Object::Integer(lhs), Object::Integer(rhs)) => lhs == rhs,
+            (Object::Float(lhs), Object::Float(rhs)) => lhs == rhs,
+            (Object::Boolean(lhs), Object::Boolean(rhs)) => lhs == rhs,
+            (Object::String(lhs), Object::String(rhs)) => lhs == rhs,
+            (Object::List(lhs), Object::List(rhs)) => lhs == rhs,
+            (Object::Function(lhs), Object::Function(rhs)) => Rc::ptr_eq(lhs, rhs),
+            (Object::NativeFunction(lhs), Object::NativeFunction(rhs)) => lhs.0 == rhs.0,
+            (Object::Null, Object::Null) | (Object::Void, Object::Void) => true,
+            _ => false
         }
     }
 }
@@ -286,15 +356,16 @@ impl PartialEq for Object {
 impl PartialOrd for Object {
     #[inline]
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        debug_assert_eq!(self.get_type(), other.get_type());
-        match self.get_type() {
-            ObjectType::Boolean | ObjectType::Integer | ObjectType::Null => self.0.partial_cmp(&other.0),
-            ObjectType::Float => self.as_f32().expect("Couldn't take as f32").partial_cmp(&other.as_f32().expect("Couldn't take as f32")),
-            ObjectType::String => self.as_str().expect("Couldn't take as string").partial_cmp(other.as_str().expect("Couldn't take as string")),
-            ObjectType::List => self.as_list().expect("Couldn't take as list").len().partial_cmp(&other.as_list().expect("Couldn't take as list").len()),
-            ObjectType::Function => None,
-            ObjectType::NativeFunction => None,
-            ObjectType::Void => None,
+        match (self, other) {
+            (Object::Boolean(lhs), Object::Boolean(rhs)) => lhs.partial_cmp(rhs),
+            (Object::Integer(lhs), Object::Integer(rhs)) => lhs.partial_cmp(rhs),
+            (Object::Float(lhs), Object::Float(rhs)) => lhs.partial_cmp(rhs),
+            (Object::Integer(lhs), Object::Float(rhs)) => (*lhs as f32).partial_cmp(rhs),
+            (Object::Float(lhs), Object::Integer(rhs)) => lhs.partial_cmp(&(*rhs as f32)),
+            (Object::String(lhs), Object::String(rhs)) => lhs.partial_cmp(rhs),
+            (Object::List(lhs), Object::List(rhs)) => lhs.len().partial_cmp(&rhs.len()),
+            (Object::Null, Object::Null) => Some(Ordering::Equal),
+            _ => None,
         }
     }
 }
@@ -302,6 +373,8 @@ impl PartialOrd for Object {
 #[derive(Debug, PartialEq)]
 pub enum Error {
     TypeError(String),
+    DivisionByZero,
+    IntegerOverflow,
 }
 
 impl std::error::Error for Error {}
@@ -309,26 +382,51 @@ impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             Error::TypeError(msg) => write!(f, "{}", msg),
+            Error::DivisionByZero => write!(f, "Attempted to divide by zero"),
+            Error::IntegerOverflow => write!(f, "Integer operation overflowed"),
         }
     }
 }
 
+/// Applies an arithmetic operator to two integers, handling overflow according to `mode`.
+/// A negative exponent produces a float, as the result is fractional
+pub fn integer_arithmetic(lhs: i32, operator: &ArithmeticOperator, rhs: i32, mode: IntegerOverflow) -> Result<Object, Error> {
+    let (checked, wrapping, saturating) = match operator {
+        ArithmeticOperator::Divide | ArithmeticOperator::Modulo if rhs == 0 => return Err(Error::DivisionByZero),
+        ArithmeticOperator::Power if rhs < 0 => return Ok(Object::float((lhs as f32).powi(rhs))),
+
+        ArithmeticOperator::Plus => (lhs.checked_add(rhs), lhs.wrapping_add(rhs), lhs.saturating_add(rhs)),
+        ArithmeticOperator::Minus => (lhs.checked_sub(rhs), lhs.wrapping_sub(rhs), lhs.saturating_sub(rhs)),
+        ArithmeticOperator::Multiply => (lhs.checked_mul(rhs), lhs.wrapping_mul(rhs), lhs.saturating_mul(rhs)),
+        ArithmeticOperator::Divide => (lhs.checked_div(rhs), lhs.wrapping_div(rhs), lhs.saturating_div(rhs)),
+        // `i32::MIN % -1` is mathematically 0, it only overflows as an intermediate step
+        ArithmeticOperator::Modulo => (lhs.checked_rem(rhs), lhs.wrapping_rem(rhs), lhs.wrapping_rem(rhs)),
+        ArithmeticOperator::Power => (lhs.checked_pow(rhs as u32), lhs.wrapping_pow(rhs as u32), lhs.saturating_pow(rhs as u32)),
+    };
+
+    match mode {
+        IntegerOverflow::Checked => checked.map(Object::integer).ok_or(Error::IntegerOverflow),
+        IntegerOverflow::Wrapping => Ok(Object::integer(wrapping)),
+        IntegerOverflow::Saturating => Ok(Object::integer(saturating)),
+    }
+}
+
 macro_rules! impl_arithmetic {
-    ($func_name:ident, $op:tt) => {
-        impl_arithmetic!($func_name, $op, (lhs, rhs) => {});
+    ($func_name:ident, $op:tt, $operator:ident) => {
+        impl_arithmetic!($func_name, $op, $operator, (lhs, rhs) => {});
     };
 
-    ($func_name:ident, $op:tt, ($lhs:ident, $rhs:ident) => { $($pat:pat => $result:expr),* }) => {
+    ($func_name:ident, $op:tt, $operator:ident, ($lhs:ident, $rhs:ident) => { $($pat:pat $(if $guard:expr)? => $result:expr),* }) => {
         pub fn $func_name(self, rhs: Self) -> Result<Object, Error> {
             
             let ($lhs, $rhs) = (self, rhs);
             
             let result = match ($lhs.get_type(), $rhs.get_type()) {
-                (ObjectType::Integer, ObjectType::Integer) => Object::integer($lhs.as_integer().expect("Couldn't take as integer") $op $rhs.as_integer().expect("Couldn't take as integer")),
+                $($pat $(if $guard)? => $result,)*
+                (ObjectType::Integer, ObjectType::Integer) => integer_arithmetic($lhs.as_integer().expect("Couldn't take as integer"), &ArithmeticOperator::$operator, $rhs.as_integer().expect("Couldn't take as integer"), IntegerOverflow::default())?,
                 (ObjectType::Float, ObjectType::Float) => Object::float($lhs.as_f32().expect("Couldn't take as f32") $op $rhs.as_f32().expect("Couldn't take as f32")),
                 (ObjectType::Float, ObjectType::Integer) => Object::float($lhs.as_f32().expect("Couldn't take as f32") $op $rhs.as_integer().expect("Couldn't take as integer") as f32),
                 (ObjectType::Integer, ObjectType::Float) => Object::float($lhs.as_integer().expect("Couldn't take as integer") as f32 $op $rhs.as_f32().expect("Couldn't take as f32")),
-                $($pat => $result,)*
                 _ => return Err(Error::TypeError(format!("Operator '&g&*{}&-&r' cannot be used for types '&g&*{:?}&-&r' and '&g&*{:?}&-&r'", stringify!($op), $lhs.get_type(), $rhs.get_type()))),
             };
 
@@ -337,18 +435,6 @@ macro_rules! impl_arithmetic {
     };
 }
 
-macro_rules! impl_logical {
-    ($func_name:ident, $op:tt) => {
-        pub fn $func_name(self, rhs: Self) -> Result<Object, Error> {
-            let result = match (self.get_type(), rhs.get_type()) {
-                (ObjectType::Boolean, ObjectType::Boolean) => Object::boolean(self.as_boolean().expect("Couldn't take as boolean") $op rhs.as_boolean().expect("Couldn't take as boolean")),
-                _ => return Err(Error::TypeError(format!("Operator '&g&*{}&-&r' cannot be used for types '&g&*{:?}&-&r' and '&g&*{:?}&-&r'", stringify!($op), self.get_type(), rhs.get_type())))
-            };
-            Ok(result)
-        }
-    };
-}
-
 macro_rules! impl_comparison {
     ($func_name:ident, $op:tt) => {
         pub fn $func_name(self, rhs: Self) -> Result<Object, Error> {
@@ -361,19 +447,20 @@ macro_rules! impl_comparison {
     };
 }
 
+#[allow(clippy::should_implement_trait)]
 impl Object {
-    impl_arithmetic!(add, +, (lhs, rhs) => {
+    impl_arithmetic!(add, +, Plus, (lhs, rhs) => {
         (ObjectType::String, _) => Object::string(&(lhs.as_str().expect("Couldn't take as str").to_string() + rhs.to_string().as_str())),
         (_, ObjectType::String) => Object::string(&(lhs.to_string() + rhs.as_str().expect("Couldn't take as str")))
     });
 
-    impl_arithmetic!(subtract, -);
-    impl_arithmetic!(multiply, *);
-    impl_arithmetic!(divide, /);
-    impl_arithmetic!(modulo, %);
+    impl_arithmetic!(subtract, -, Minus);
+    impl_arithmetic!(multiply, *, Multiply);
+    impl_arithmetic!(divide, /, Divide);
+    impl_arithmetic!(modulo, %, Modulo);
     pub fn power(self, rhs: Self) -> Result<Object, Error> {
         let result = match (self.get_type(), rhs.get_type()) {
-            (ObjectType::Integer, ObjectType::Integer) => Object::integer(self.as_integer().expect("Couldn't take as integer").pow(rhs.as_integer().expect("Couldn't take as integer") as u32)),
+            (ObjectType::Integer, ObjectType::Integer) => integer_arithmetic(self.as_integer().expect("Couldn't take as integer"), &ArithmeticOperator::Power, rhs.as_integer().expect("Couldn't take as integer"), IntegerOverflow::default())?,
             (ObjectType::Float, ObjectType::Float) => Object::float(self.as_f32().expect("Couldn't take as f32").powf(rhs.as_f32().expect("Couldn't take as f32"))),
             (ObjectType::Float, ObjectType::Integer) => Object::float(self.as_f32().expect("Couldn't take as f32").powf(rhs.as_integer().expect("Couldn't take as integer") as f32)),
             (ObjectType::Integer, ObjectType::Float) => Object::float((self.as_integer().expect("Couldn't take as integer") as f32).powf(rhs.as_f32().expect("Couldn't take as f32"))),
@@ -387,9 +474,33 @@ impl Object {
     impl_comparison!(greater_than_equal, >=);
     impl_comparison!(lesser_than, <);
     impl_comparison!(lesser_than_equal, <=);
-    impl_comparison!(equal, ==);
-    impl_comparison!(not_equal, !=);
 
-    impl_logical!(and, &&);
-    impl_logical!(or, ||);
+    pub fn equal(self, rhs: Self) -> Result<Object, Error> {
+        Ok(Object::boolean(self.equals(&rhs)))
+    }
+
+    pub fn not_equal(self, rhs: Self) -> Result<Object, Error> {
+        Ok(Object::boolean(!self.equals(&rhs)))
+    }
+
+    /// Checks whether `self` is an element of a list, or a substring of a string
+    pub fn is_in(self, rhs: Self) -> Result<Object, Error> {
+        let result = match (&self, &rhs) {
+            (_, Object::List(elements)) => elements.iter().any(|element| element.equals(&self)),
+            (Object::String(needle), Object::String(haystack)) => haystack.contains(needle.as_str()),
+            _ => return Err(Error::TypeError(format!("Operator '&g&*{}&-&r' cannot be used for types '&g&*{:?}&-&r' and '&g&*{:?}&-&r'", "in", self.get_type(), rhs.get_type()))),
+        };
+
+        Ok(Object::boolean(result))
+    }
+
+    /// `self` if it's falsy, otherwise `rhs`, keeping the type of whichever is returned
+    pub fn and(self, rhs: Self) -> Result<Object, Error> {
+        Ok(if self.is_truthy() { rhs } else { self })
+    }
+
+    /// `self` if it's truthy, otherwise `rhs`, e.g. `0 || "default"` is `"default"`
+    pub fn or(self, rhs: Self) -> Result<Object, Error> {
+        Ok(if self.is_truthy() { self } else { rhs })
+    }
 }
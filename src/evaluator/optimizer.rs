@@ -0,0 +1,163 @@
+use crate::parser::ast::{self, Assignment, BinaryExpression, BlockStatement, CallExpression, ElseStatement, Expression, ExpressionStatement, ForStatement, FunctionCallExpression, FunctionDeclareExpression, IfStatement, IndexExpression, ListLiteral, Literal, MatchArm, MatchExpression, MethodCallExpression, Node, Operator, PropertyExpression, ReturnStatement, ThrowStatement, TryStatement, UnaryExpression, WhileStatement};
+
+use super::{object::{Object, ObjectType}, Evaluator};
+
+/// Folds constant sub-expressions in place, e.g. `2 * 3 + 1` becomes `IntegerLiteral(7)`.
+///
+/// This has to run before the program is handed to an `Evaluator`, as the evaluator borrows the AST,
+/// which `Evaluator::folded` takes care of.
+/// Expressions which would fail (division by zero, mismatched types) are left untouched,
+/// so the evaluator still reports them as runtime errors.
+pub fn fold_constants(ast: &mut [Node]) {
+    for node in ast {
+        fold_node(node);
+    }
+}
+
+fn fold_node(node: &mut Node) {
+    match node {
+        Node::Program(nodes) => fold_constants(nodes),
+        Node::BlockStatement(block) => fold_block(block),
+        Node::ExpressionStatement(ExpressionStatement(expression, _)) => fold_expression(expression),
+        Node::ReturnStatement(ReturnStatement(Some(expression), _)) => fold_expression(expression),
+        Node::IfStatement(IfStatement(condition, body, otherwise, _)) => {
+            fold_expression(condition);
+            fold_block(body);
+
+            if let Some(otherwise) = otherwise {
+                fold_node(otherwise);
+            }
+        },
+        Node::ElseStatement(ElseStatement(body)) => fold_block(body),
+        Node::WhileStatement(WhileStatement(condition, body, _)) => {
+            fold_expression(condition);
+            fold_block(body);
+        },
+        Node::ForStatement(ForStatement(setter, condition, assignment, body, _)) => {
+            fold_expression(setter);
+            fold_expression(condition);
+            fold_expression(assignment);
+            fold_block(body);
+        },
+        Node::ThrowStatement(ThrowStatement(expression, _)) => fold_expression(expression),
+        Node::TryStatement(TryStatement(body, _, handler, _)) => {
+            fold_block(body);
+            fold_block(handler);
+        },
+        _ => {}
+    }
+}
+
+fn fold_block(block: &mut BlockStatement) {
+    fold_constants(&mut block.0);
+}
+
+fn fold_expression(expression: &mut Expression) {
+    let folded = match expression {
+        Expression::AssignmentExpr(Assignment(_, value, _)) => {
+            fold_node(value);
+            None
+        },
+        Expression::LiteralExpr(Literal::List(ListLiteral(elements))) => {
+            elements.iter_mut().for_each(fold_expression);
+            None
+        },
+        Expression::BlockExpr(block) => {
+            fold_block(block);
+            None
+        },
+        Expression::FunctionCallExpr(FunctionCallExpression(_, arguments, _)) => {
+            arguments.iter_mut().for_each(fold_expression);
+            None
+        },
+        Expression::CallExpr(CallExpression(callee, arguments, _)) => {
+            fold_expression(callee);
+            arguments.iter_mut().for_each(fold_expression);
+            None
+        },
+        Expression::IndexExpr(IndexExpression(target, index, _)) => {
+            fold_expression(target);
+            fold_expression(index);
+            None
+        },
+        Expression::MethodCallExpr(MethodCallExpression(receiver, _, arguments, _)) => {
+            fold_expression(receiver);
+            arguments.iter_mut().for_each(fold_expression);
+            None
+        },
+        Expression::PropertyExpr(PropertyExpression(receiver, _, _)) => {
+            fold_expression(receiver);
+            None
+        },
+        Expression::FunctionDeclareExpr(FunctionDeclareExpression(_, _, body)) => {
+            fold_block(body);
+            None
+        },
+        Expression::GroupExpr(inner) => {
+            fold_expression(inner);
+
+            match inner.as_ref() {
+                Expression::LiteralExpr(literal) => Some(literal.to_owned()),
+                _ => None
+            }
+        },
+        Expression::UnaryExpr(UnaryExpression(operator, inner)) => {
+            fold_expression(inner);
+
+            match (operator, inner.as_ref()) {
+                (Operator::Arithmetic(ast::ArithmeticOperator::Minus), Expression::LiteralExpr(Literal::Integer(value))) =>
+                    value.0.checked_neg().map(|value| Literal::Integer(ast::IntegerLiteral(value))),
+                (Operator::Arithmetic(ast::ArithmeticOperator::Minus), Expression::LiteralExpr(Literal::Float(value))) =>
+                    Some(Literal::Float(ast::FloatLiteral(-value.0))),
+                _ => None
+            }
+        },
+        Expression::BinaryExpr(BinaryExpression(left, operator, right, _, _)) => {
+            fold_expression(left);
+            fold_expression(right);
+
+            match (as_constant(left), as_constant(right)) {
+                (Some(lhs), Some(rhs)) => match Evaluator::eval_operator(lhs, operator, rhs) {
+                    Ok(result) => to_literal(&result),
+                    Err(_) => None
+                },
+                _ => None
+            }
+        },
+        Expression::MatchExpr(MatchExpression(scrutinee, arms, _)) => {
+            fold_expression(scrutinee);
+            arms.iter_mut().for_each(|MatchArm(_, body)| fold_expression(body));
+            None
+        },
+        Expression::IdentifierExpr(..) | Expression::LiteralExpr(_) => None,
+    };
+
+    if let Some(literal) = folded {
+        *expression = Expression::LiteralExpr(literal);
+    }
+}
+
+fn as_constant(expression: &Expression) -> Option<Object> {
+    match expression {
+        Expression::LiteralExpr(literal) => match literal {
+            Literal::Integer(value) => Some(Object::integer(value.0)),
+            Literal::Float(value) => Some(Object::float(value.0)),
+            Literal::Boolean(value) => Some(Object::boolean(value.0)),
+            Literal::String(value) => Some(Object::string(&value.0)),
+            Literal::Null => Some(Object::null()),
+            Literal::List(_) => None,
+        },
+        _ => None
+    }
+}
+
+fn to_literal(object: &Object) -> Option<Literal> {
+    Some(match object.get_type() {
+        ObjectType::Integer => Literal::Integer(ast::IntegerLiteral(object.as_integer()?)),
+        ObjectType::Float => Literal::Float(ast::FloatLiteral(object.as_f32()?)),
+        ObjectType::Boolean => Literal::Boolean(ast::BooleanLiteral(object.as_boolean()?)),
+        ObjectType::String => Literal::String(ast::StringLiteral(object.as_str()?.to_string())),
+        ObjectType::Null => Literal::Null,
+        _ => return None
+    })
+}
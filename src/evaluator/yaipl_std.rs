@@ -1,72 +1,280 @@
-use super::{environment::Environment, object::{NativeFunctionObject, Object}};
+use std::rc::Rc;
 
-pub fn initialize<'a>(env: &mut Environment<'a>) {
+use crate::{error, errors::{EvaluatorError, ExitSignal}, lexer::token::Position};
+
+use super::{count_native_iterations, environment::Environment, object::{NativeFunctionObject, Object, ObjectType}, EvaluatorResult};
+
+pub fn initialize(env: &mut Environment) {
     macro_rules! function {
         ($name:literal, [$($args:tt),*], ($arg_param:tt) => $body:block) => {
-            function!($name, [$($args),*], (_, $arg_param) => $body);
+            function!($name, [$($args),*], (_, $arg_param, _) => $body);
         };
 
         ($name:literal, [$($args:tt),*], ($env:tt, $arg_param:tt) => $body:block) => {
+            function!($name, [$($args),*], ($env, $arg_param, _) => $body);
+        };
+
+        ($name:literal, [$($args:tt),*], ($env:tt, $arg_param:tt, $pos:tt) => $body:block) => {
             {
-                let function = NativeFunctionObject(concat!("__fc_", $name), vec!($($args.to_string()),*), |$env, $arg_param| {
+                let function = NativeFunctionObject(concat!("__fc_", $name).to_string(), vec!($($args.to_string()),*), Rc::new(|$env, $arg_param, $pos| {
                     $body
-                });
+                }));
 
-                let func_box = Box::new(function);
-                let func_ptr = Box::into_raw(func_box);
-
-                let object = Object::native_function(unsafe { &*func_ptr });
-
-                env.set(concat!("__fc_", $name), object);
+                env.set(concat!("__fc_", $name), Object::native_function(function));
             }
         };
     }
 
     function!("yaipl_debug_env", [], (env, _) => {
         println!("{:#?}", env);
-        Object::void()
+        Ok(Object::void())
     });
 
-    function!("print", ["arg"], (args) => {
-        let value = if args.len() > 0 {
-            args[0].to_string()
-        } else {
-            String::from("")
-        };
+    function!("print", ["...args"], (args) => {
+        print!("{}", join_arguments(&args));
+        Ok(Object::void())
+    });
 
-        print!("{}", value);
-        Object::void()
+    function!("println", ["...args"], (args) => {
+        println!("{}", join_arguments(&args));
+        Ok(Object::void())
     });
 
-    function!("println", ["arg"], (args) => {
-        let value = if args.len() > 0 {
-            args[0].to_string()
-        } else {
-            String::from("")
-        };
+    // Prints to stderr so it doesn't mix with the program's output
+    function!("dbg", ["arg"], (_, args, pos) => {
+        let value = args.into_iter().next().unwrap_or(Object::void());
 
-        println!("{}", value);
-        Object::void()
+        eprintln!("{}", debug_line(pos, &value));
+        Ok(value)
     });
 
     function!("typeof", ["arg"], (args) => {
-        let value: String = if args.len() > 0 {
+        let value: String = if !args.is_empty() {
             args[0].get_type().to_string()
         } else {
             String::new()
         };
 
-        Object::string(&value)
+        Ok(Object::string(&value))
     });
 
     function!("sleep", ["ms"], (args) => {
-        let ms = if args.len() > 0 {
+        let ms = if !args.is_empty() {
             args[0].to_string().parse::<u64>().unwrap()
         } else {
             0
         };
 
         std::thread::sleep(std::time::Duration::from_millis(ms));
-        Object::void()
+        Ok(Object::void())
+    });
+
+    function!("contains", ["haystack", "needle"], (args) => {
+        let (haystack, needle) = haystack_and_needle(&args)?;
+
+        Ok(needle.to_owned().is_in(haystack.to_owned())?)
+    });
+
+    function!("index_of", ["haystack", "needle"], (args) => {
+        let (haystack, needle) = haystack_and_needle(&args)?;
+
+        let index = match (haystack, needle) {
+            (Object::List(elements), _) => elements.iter().position(|element| element.equals(needle)),
+            // Count characters rather than bytes, so the index can be used with `slice`
+            (Object::String(string), Object::String(needle)) => string.find(needle.as_str()).map(|byte| string[..byte].chars().count()),
+            _ => None
+        };
+
+        Ok(Object::integer(index.map_or(-1, |index| index as i32)))
+    });
+
+    function!("assert_eq", ["left", "right"], (args) => {
+        let left = args.first().cloned().unwrap_or(Object::void());
+        let right = args.get(1).cloned().unwrap_or(Object::void());
+
+        if !left.equals(&right) {
+            error!(EvaluatorError::AssertionFailed {
+                left: left.to_string(),
+                right: right.to_string(),
+            })
+        }
+
+        Ok(Object::void())
+    });
+
+    function!("slice", ["value", "start", "end"], (args) => {
+        let length = match args.first() {
+            Some(Object::List(elements)) => elements.len(),
+            Some(Object::String(string)) => string.chars().count(),
+            other => error!(EvaluatorError::InvalidType {
+                expected: vec![ObjectType::List, ObjectType::String],
+                found: other.map(Object::get_type).unwrap_or(ObjectType::Void),
+            })
+        } as i64;
+
+        // Negative bounds count from the end, and out of range bounds are clamped like in Python
+        let mut bounds = [0, length];
+        for (bound, arg) in bounds.iter_mut().zip(args.iter().skip(1)) {
+            *bound = match arg {
+                Object::Integer(index) if *index < 0 => (length + *index as i64).max(0),
+                Object::Integer(index) => (*index as i64).min(length),
+                Object::Null => continue,
+                _ => error!(EvaluatorError::InvalidType {
+                    expected: vec![ObjectType::Integer, ObjectType::Null],
+                    found: arg.get_type(),
+                })
+            };
+        }
+
+        let [start, end] = bounds.map(|bound| bound as usize);
+        let end = end.max(start);
+
+        Ok(match &args[0] {
+            Object::List(elements) => Object::list(elements[start..end].to_vec()),
+            Object::String(string) => Object::string(&string.chars().skip(start).take(end - start).collect::<String>()),
+            _ => Object::void()
+        })
+    });
+
+    function!("join", ["list", "separator"], (args) => {
+        let elements = match args.first() {
+            Some(Object::List(elements)) => elements,
+            other => error!(EvaluatorError::InvalidType {
+                expected: vec![ObjectType::List],
+                found: other.map(Object::get_type).unwrap_or(ObjectType::Void),
+            })
+        };
+
+        let separator = match args.get(1) {
+            Some(Object::String(separator)) => separator.as_str(),
+            None => "",
+            Some(other) => error!(EvaluatorError::InvalidType {
+                expected: vec![ObjectType::String],
+                found: other.get_type(),
+            })
+        };
+
+        let mut strings: Vec<&str> = Vec::with_capacity(elements.len());
+        for (index, element) in elements.iter().enumerate() {
+            match element.as_str() {
+                Some(string) => strings.push(string),
+                None => error!(EvaluatorError::InvalidElementType {
+                    index,
+                    expected: ObjectType::String,
+                    found: element.get_type(),
+                })
+            }
+        }
+
+        Ok(Object::string(&strings.join(separator)))
+    });
+
+    function!("range", ["start", "end", "step"], (_, args, pos) => {
+        // A single argument is the end, like `range(5)` for 0 to 4
+        let mut bounds = [0, 0, 1];
+        let skip = if args.len() == 1 { 1 } else { 0 };
+        for (bound, arg) in bounds.iter_mut().skip(skip).zip(args.iter()) {
+            *bound = match arg {
+                Object::Integer(value) => *value as i64,
+                _ => error!(EvaluatorError::InvalidType {
+                    expected: vec![ObjectType::Integer],
+                    found: arg.get_type(),
+                })
+            };
+        }
+
+        let [start, end, step] = bounds;
+        if step == 0 {
+            error!(EvaluatorError::ZeroStep)
+        }
+
+        // The end is exclusive, and a negative step counts down towards it
+        let len = if step > 0 && end > start {
+            (end - start + step - 1) / step
+        } else if step < 0 && start > end {
+            (start - end - step - 1) / -step
+        } else {
+            0
+        } as usize;
+
+        // Each element counts as a loop iteration, so a huge range can't get around the limit
+        count_native_iterations(len, pos)?;
+
+        let mut elements = Vec::with_capacity(len);
+        let mut value = start;
+        while (step > 0 && value < end) || (step < 0 && value > end) {
+            elements.push(Object::integer(value as i32));
+            value += step;
+        }
+
+        Ok(Object::list(elements))
+    });
+
+    function!("first", ["list"], (args) => {
+        Ok(list_argument(&args)?.first().cloned().unwrap_or(Object::null()))
     });
-}
\ No newline at end of file
+
+    function!("last", ["list"], (args) => {
+        Ok(list_argument(&args)?.last().cloned().unwrap_or(Object::null()))
+    });
+
+    function!("rest", ["list"], (args) => {
+        Ok(Object::list(list_argument(&args)?.iter().skip(1).cloned().collect()))
+    });
+
+    function!("exit", ["code"], (args) => {
+        let code = match args.first() {
+            Some(Object::Integer(code)) => *code,
+            Some(other) => error!(EvaluatorError::InvalidType {
+                expected: vec![ObjectType::Integer],
+                found: other.get_type(),
+            }),
+            None => 0
+        };
+
+        error!(ExitSignal(code))
+    });
+}
+
+/// Formats what `dbg` prints for a value, e.g. `[3:5] Integer(5)`
+pub(super) fn debug_line(pos: &Position, value: &Object) -> String {
+    format!("[{}] {:?}", pos, value)
+}
+
+/// Formats the arguments of `print` and `println`, separated by spaces
+pub(super) fn join_arguments(args: &[Object]) -> String {
+    args.iter().map(Object::to_string).collect::<Vec<String>>().join(" ")
+}
+
+/// Takes the elements of the first argument, which must be a list
+fn list_argument(args: &[Object]) -> EvaluatorResult<&Vec<Object>> {
+    match args.first() {
+        Some(Object::List(elements)) => Ok(elements),
+        other => error!(EvaluatorError::InvalidType {
+            expected: vec![ObjectType::List],
+            found: other.map(Object::get_type).unwrap_or(ObjectType::Void),
+        })
+    }
+}
+
+/// Validates the arguments of `contains` and `index_of`, which search a list for an element or a string for a substring
+fn haystack_and_needle(args: &[Object]) -> EvaluatorResult<(&Object, &Object)> {
+    let (Some(haystack), Some(needle)) = (args.first(), args.get(1)) else {
+        error!(EvaluatorError::InvalidType {
+            expected: vec![ObjectType::List, ObjectType::String],
+            found: ObjectType::Void,
+        })
+    };
+
+    match (haystack, needle) {
+        (Object::List(_), _) | (Object::String(_), Object::String(_)) => Ok((haystack, needle)),
+        (Object::String(_), _) => error!(EvaluatorError::InvalidType {
+            expected: vec![ObjectType::String],
+            found: needle.get_type(),
+        }),
+        _ => error!(EvaluatorError::InvalidType {
+            expected: vec![ObjectType::List, ObjectType::String],
+            found: haystack.get_type(),
+        })
+    }
+}
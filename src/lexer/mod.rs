@@ -1,22 +1,44 @@
-use crate::{error, errors::{DynamicError, LexerError}};
+use std::collections::VecDeque;
+
+use crate::{error, errors::{DynamicError, LexerError, Warning}, utils::unwrap_result};
 use self::token::{Position, Token, TokenLiteral, TokenType, Tokens};
 
 pub mod token;
 
+#[derive(Debug, Clone, Default)]
+pub struct LexerOptions {
+    /// Warn about lines whose leading whitespace mixes tabs and spaces
+    pub warn_mixed_indentation: bool,
+}
+
 pub struct Lexer {
+    pub options: LexerOptions,
     pub tokens: Tokens,
-    chars: Vec<char>,
+    pub warnings: Vec<Warning>,
+    /// Remaining input, consumed from the front
+    chars: VecDeque<char>,
+    /// Both one-based. `col` is the column of the last consumed character, so it is 0 until a line's first one
     line: usize,
     col: usize,
+    /// Byte offsets into the input of the last consumed character and of the next one
+    offset: usize,
+    next_offset: usize,
+    /// Where the last newline was, as the position moves on to the next line once it is consumed
+    line_end: Position,
 }
 
 impl Lexer {
-    pub fn from(input: &str) -> Lexer {
+    pub fn from(input: impl AsRef<str>) -> Lexer {
         Lexer {
+            options: LexerOptions::default(),
             tokens: Vec::new(),
-            chars: input.chars().collect::<Vec<char>>(),
+            warnings: Vec::new(),
+            chars: input.as_ref().chars().collect::<VecDeque<char>>(),
             line: 1,
-            col: 0
+            col: 0,
+            offset: 0,
+            next_offset: 0,
+            line_end: Position::from(1, 1),
         }
     }
 
@@ -31,21 +53,20 @@ impl Lexer {
     }
 
     pub fn tokenize(&mut self) -> Result<&Tokens, DynamicError> {
+        if self.options.warn_mixed_indentation {
+            self.check_indentation();
+        }
+
         while !self.chars.is_empty() {
             let mut char = self.remove_char(0)?;
 
             if char == '\n' {
+                self.end_line();
                 continue;
             }
     
             let matched_tokens: Tokens = match self.match_char(char) {
-                Some((token, len)) => vec![
-                    Token::from_pos(
-                        token, 
-                        self.get_pos(), 
-                        self.get_pos_offset(len as usize)
-                    )
-                ],
+                Some((token, len)) => vec![self.char_token(char, token, len)],
     
                 None => {
                     let mut ret: Tokens = Vec::new();
@@ -54,35 +75,33 @@ impl Lexer {
                     if char == '"' {
                         let str = self.parse_string(&mut char)?;
 
+                        // Strings can span lines, so the end is just past wherever the closing quote was found
                         ret.push(Token::from_value_pos(
                             TokenType::String, 
                             start, 
-                            self.get_pos_offset(str.chars().count()),
+                            self.get_pos_offset(1),
                             Some(TokenLiteral::String(str))
                         ));
                     } else {
-                        let mut word = self.parse_word(&mut char)?;
+                        let (mut word, matched) = self.parse_word(&mut char)?;
                         
                         if self.is_comment(&char) {
                             while !self.chars.is_empty() && char != '\n' {
                                 char = self.remove_char(0)?;
                             }
-                            
+
+                            let pos = if char == '\n' { self.line_end.to_owned() } else { self.get_pos_offset(1) };
                             self.tokens.push(Token::from_pos(
                                 TokenType::EndOfLine, 
-                                self.get_pos(), 
-                                self.get_pos_offset(1)
+                                pos.to_owned(),
+                                pos.shifted(1)
                             ));
                             continue;
                         }
                         
-                        if let Some((token, len)) = self.match_char(char) {
-                            ret.push(Token::from_pos(
-                                token, 
-                                self.get_pos(), 
-                                self.get_pos_offset(len as usize))
-                            );
-                        } else if char != ' ' && char != '\n' {
+                        if let Some((token, len)) = matched.or_else(|| self.match_char(char)) {
+                            ret.push(self.char_token(char, token, len));
+                        } else if !char.is_whitespace() {
                             word.push(char)
                         }
                         
@@ -90,22 +109,41 @@ impl Lexer {
                             continue;
                         }
 
-                        let end: Position = self.get_pos();
+                        // Words never span lines, so they end as many characters and bytes after the start
+                        let end = Position::with_offset(start.line, start.col + word.chars().count(), start.offset + word.len());
             
-                        ret.push(if let Ok(num) = word.replace("_", "").parse::<i32>() {
+                        let number = word.replace("_", "");
+                        let negative = self.tokens.last().is_some_and(|token| token.token_type == TokenType::Minus);
+                        if let Some(expected) = Self::out_of_range(&number, negative) {
+                            error!(LexerError::NumberOutOfRange {
+                                literal: word,
+                                expected,
+                                pos: start
+                            })
+                        }
+
+                        // Words like `inf` and `nan` parse as floats too, but they are identifiers
+                        let is_number = word.starts_with(|char: char| char.is_ascii_digit() || char == '.');
+
+                        ret.push(if let Ok(num) = number.parse::<u32>() {
                             Token::from_value_pos(
                                 TokenType::Integer, 
                                 start,
-                                self.get_pos_offset(num.to_string().chars().count()),
+                                end,
                                 Some(TokenLiteral::Integer(num))
                             )
-                        } else if let Ok(num) = word.replace("_", "").parse::<f32>() {
+                        } else if let Some(num) = number.parse::<f32>().ok().filter(|_| is_number) {
                             Token::from_value_pos(
                                 TokenType::Float, 
                                 start, 
-                                self.get_pos_offset(num.to_string().chars().count()),
+                                end,
                                 Some(TokenLiteral::Float(num))
                             )
+                        } else if is_number {
+                            error!(LexerError::InvalidNumber {
+                                literal: word,
+                                pos: start
+                            })
                         } else {
                             let (token_type, value) = match word.as_str() {
                                 "true" => (TokenType::Boolean, Some(TokenLiteral::Boolean(true))),
@@ -121,8 +159,16 @@ impl Lexer {
                                 "return" => (TokenType::Return, None),
                                 "break" => (TokenType::Break, None),
                                 "continue" => (TokenType::Continue, None),
+                                "match" => (TokenType::Match, None),
+                                "in" => (TokenType::In, None),
+                                "throw" => (TokenType::Throw, None),
+                                "try" => (TokenType::Try, None),
+                                "catch" => (TokenType::Catch, None),
         
-                                _ => (TokenType::Symbol, Some(TokenLiteral::String(word)))
+                                _ => {
+                                    Self::check_identifier(&word, &start)?;
+                                    (TokenType::Symbol, Some(TokenLiteral::String(word)))
+                                }
                             };
 
                             Token::from_value_pos(token_type, start, end, value)
@@ -140,17 +186,25 @@ impl Lexer {
                         continue;
                     }
                 }
+
+                // A line starting with e.g. `.len()` or `+ 1` takes back the end of line before it.
+                // Only ones made by a newline or comment, as those from `;` hold its value
+                if token.token_type.continues_line() && self.tokens.last().is_some_and(|last| last.token_type == TokenType::EndOfLine && last.value.is_none()) {
+                    self.tokens.pop();
+                }
     
                 self.tokens.push(token.to_owned());
             }
+
+            // A word can be ended by the newline itself
+            if char == '\n' {
+                self.end_line();
+            }
         }
     
         if let Some(first) = self.tokens.first() {
-            match first.token_type {
-                TokenType::EndOfLine => {
-                    self.tokens.remove(0);
-                },
-                _ => {}
+            if first.token_type == TokenType::EndOfLine {
+                self.tokens.remove(0);
             }
         }
     
@@ -161,8 +215,8 @@ impl Lexer {
                     self.tokens.push(
                         Token::from_pos(
                             TokenType::EndOfLine, 
-                            self.get_pos(),
-                            self.get_pos_offset(1)
+                            self.get_pos_offset(1),
+                            self.get_pos_offset(2)
                         )
                     )
                 },
@@ -170,32 +224,118 @@ impl Lexer {
         }
 
         self.tokens.push(
-            Token::from_pos(TokenType::EndOfFile, self.get_pos(), self.get_pos_offset(1))
+            Token::from_pos(TokenType::EndOfFile, self.get_pos_offset(1), self.get_pos_offset(2))
         );
 
         Ok(&self.tokens)
     }
 
+    /// A newline ends a statement like `;` does, but only after a token which can end one.
+    /// This way expressions can still span lines after an operator, comma or opening bracket
+    fn end_line(&mut self) {
+        if self.tokens.last().is_some_and(|token| token.token_type.ends_statement()) {
+            let pos = self.line_end.to_owned();
+            self.tokens.push(Token::from_pos(
+                TokenType::EndOfLine,
+                pos.to_owned(),
+                pos.shifted(1)
+            ));
+        }
+    }
+
+    /// Checks whether a numeric literal overflows the type it would be lexed as,
+    /// rather than letting it silently become a float or `inf`
+    /// After a minus the magnitude of the smallest integer is allowed too, which is one more than the largest
+    fn out_of_range(number: &str, negative: bool) -> Option<TokenType> {
+        if !number.starts_with(|char: char| char.is_ascii_digit() || char == '.') {
+            return None;
+        }
+
+        if number.chars().all(|char| char.is_ascii_digit()) {
+            let max = i32::MAX.unsigned_abs() + u32::from(negative);
+            return number.parse::<u32>().map_or(true, |num| num > max).then_some(TokenType::Integer);
+        }
+
+        match number.parse::<f32>() {
+            Ok(num) if num.is_infinite() => Some(TokenType::Float),
+            _ => None
+        }
+    }
+
+    fn check_indentation(&mut self) {
+        let input: String = self.chars.iter().collect();
+
+        let mut offset = self.next_offset;
+        for (index, line) in input.split('\n').enumerate() {
+            let indent: Vec<char> = line.chars().take_while(|char| *char == ' ' || *char == '\t').collect();
+
+            if indent.contains(&' ') && indent.contains(&'\t') {
+                self.warnings.push(Warning::MixedIndentation {
+                    pos: Position::with_offset(self.line + index, 1, offset)
+                });
+            }
+
+            offset += line.len() + 1;
+        }
+    }
+
     fn remove_char(&mut self, index: usize) -> Result<char, DynamicError> {
-        if index > self.chars.len() {
+        if index >= self.chars.len() {
             error!(LexerError::OutOfBounds { index: index.to_string() })
         }
 
         self.pos_advance(1);
-        let char = self.chars.remove(0);
+        let char = self.chars.remove(index);
         
-        Ok(char)
+        unwrap_result(char)
     }
 
-    fn parse_word(&mut self, char: &mut char) -> Result<String, DynamicError> {
+    /// Reads a word up to whitespace, a comment or a token. A token's match is returned along with
+    /// the word, as matching it consumes the rest of a longer token such as the second `=` of `==`
+    fn parse_word(&mut self, char: &mut char) -> Result<(String, Option<(TokenType, u8)>), DynamicError> {
         let mut word = String::new();
 
-        while !self.chars.is_empty() && !char.is_whitespace() && !self.is_comment(&char) && self.match_char(char.to_owned()).is_none() {
+        while !self.chars.is_empty() && !char.is_whitespace() && !self.is_comment(char) {
+            if let Some(matched) = self.match_char(char.to_owned()) {
+                return Ok((word, Some(matched)));
+            }
+
             word.push(char.to_owned());
+
+            // A number ends where a letter starts, so `1x` is `1` followed by `x`. The whitespace
+            // left in `char` makes the caller start the next word at the letter
+            if word.starts_with(|char: char| char.is_ascii_digit()) && self.number_ends() {
+                *char = ' ';
+                break;
+            }
+
             *char = self.remove_char(0)?;
         };
 
-        Ok(word)
+        Ok((word, None))
+    }
+
+    /// Whether the next character can't continue a number. An `e` followed by a digit is an exponent, e.g. `1e5`
+    fn number_ends(&self) -> bool {
+        match (self.chars.front(), self.chars.get(1)) {
+            (Some('e' | 'E'), Some(next)) if next.is_ascii_digit() => false,
+            (Some(next), _) => next.is_alphabetic(),
+            _ => false
+        }
+    }
+
+    /// Identifiers start with a letter or `_`, followed by any number of letters, digits or `_`
+    fn check_identifier(word: &str, start: &Position) -> Result<(), DynamicError> {
+        for (index, (byte, character)) in word.char_indices().enumerate() {
+            if !(character.is_alphabetic() || character == '_' || (index > 0 && character.is_alphanumeric())) {
+                error!(LexerError::InvalidCharacter {
+                    character,
+                    pos: Position::with_offset(start.line, start.col + index, start.offset + byte)
+                })
+            }
+        }
+
+        Ok(())
     }
 
     fn is_comment(&self, char: &char) -> bool {
@@ -207,11 +347,13 @@ impl Lexer {
         *char = self.remove_char(0)?;
 
         while !self.chars.is_empty() {
-            if !builder.ends_with("\\") && char == &'"' {
+            // Escaped quotes are consumed below, so any quote reaching this point closes the string
+            if char == &'"' {
                 break;
             }
 
             if char == &'\\' {
+                let pos = self.get_pos();
                 *char = self.remove_char(0)?;
                 match char {
                     'b' => builder.push('\u{0008}'),
@@ -222,23 +364,8 @@ impl Lexer {
                     '\'' => builder.push('\''),
                     '\"' => builder.push('\"'),
                     '\\' => builder.push('\\'),
-                    'u' => {
-                        let mut hex = String::new();
-                        
-                        for _ in 0..4 {
-                            hex.push(self.remove_char(0)?);
-                        }
-                        
-                        let unicode = match u32::from_str_radix(&hex, 16) {
-                            Ok(unicode) => unicode,
-                            Err(_) => error!(LexerError::InvalidCharacter { 
-                                character: char.to_owned(), 
-                                pos: self.get_pos()
-                            })
-                        };
-
-                        builder.push(std::char::from_u32(unicode).unwrap());
-                    },
+                    'x' => builder.push(self.parse_hex_escape(pos)?),
+                    'u' => builder.push(self.parse_unicode_escape(pos)?),
                     _ => builder.push(char.to_owned())
                 }
                 *char = self.remove_char(0)?;
@@ -252,14 +379,62 @@ impl Lexer {
         Ok(builder)
     }
 
+    /// Decodes `\xNN`, two hex digits naming an ASCII character
+    fn parse_hex_escape(&mut self, pos: Position) -> Result<char, DynamicError> {
+        let mut hex = String::from("x");
+        for _ in 0..2 {
+            hex.push(self.remove_char(0)?);
+        }
+
+        match u8::from_str_radix(&hex[1..], 16) {
+            Ok(byte) if byte.is_ascii() => Ok(byte as char),
+            _ => error!(LexerError::InvalidEscape { escape: hex, pos })
+        }
+    }
+
+    /// Decodes `\uNNNN` or `\u{N...}` with up to six hex digits, naming a Unicode scalar value
+    fn parse_unicode_escape(&mut self, pos: Position) -> Result<char, DynamicError> {
+        let mut hex = String::from("u");
+
+        if self.chars.front() == Some(&'{') {
+            hex.push(self.remove_char(0)?);
+
+            loop {
+                let char = self.remove_char(0)?;
+                hex.push(char);
+
+                if char == '}' {
+                    break;
+                }
+
+                if !char.is_ascii_hexdigit() || hex.len() > 8 {
+                    error!(LexerError::InvalidEscape { escape: hex, pos })
+                }
+            }
+        } else {
+            for _ in 0..4 {
+                hex.push(self.remove_char(0)?);
+            }
+        }
+
+        let digits = hex[1..].trim_start_matches('{').trim_end_matches('}');
+        match u32::from_str_radix(digits, 16).ok().and_then(char::from_u32) {
+            Some(char) if !digits.is_empty() => Ok(char),
+            _ => error!(LexerError::InvalidEscape { escape: hex, pos })
+        }
+    }
+
     fn get_pos(&self) -> Position {
-        Position::from(self.line, self.col)
+        Position::with_offset(self.line, self.col, self.offset)
     }
 
+    /// The position `amount` characters after the last consumed one, those after the next
+    /// character taken to be one byte long
     fn get_pos_offset(&self, amount: usize) -> Position {
-        let mut pos = self.get_pos();
-        pos.col += amount;
-        pos
+        match amount {
+            0 => self.get_pos(),
+            _ => Position::with_offset(self.line, self.col + amount, self.next_offset + amount - 1)
+        }
     }
 
     fn pos_advance(&mut self, amount: usize) {
@@ -268,14 +443,16 @@ impl Lexer {
                 break;
             }
             
-            let char = self.chars.get(0).unwrap_or(&' ');
-
-            if char == &'\n' {
-                self.line += 1;
-                self.col = 0;
-            } else {
-                self.col += 1;
+            let char = *self.chars.front().unwrap_or(&' ');
+            if char == '\n' {
+                self.line_end = self.get_pos_offset(1);
             }
+
+            let mut pos = self.get_pos();
+            pos.advance(char);
+            (self.line, self.col) = pos.to_tuple();
+            self.offset = self.next_offset;
+            self.next_offset += char.len_utf8();
         }
     }
 
@@ -284,9 +461,10 @@ impl Lexer {
             return false;
         }
     
-        if let Some(next_char) = self.chars.get(0) {
-            if next_char.to_owned() == char {
-                self.chars.remove(0);
+        if let Some(next_char) = self.chars.front() {
+            if *next_char == char {
+                self.pos_advance(1);
+                self.chars.pop_front();
                 return true;
             }
         }
@@ -294,6 +472,18 @@ impl Lexer {
         false
     }
     
+    /// Builds a token found by `match_char`, once all `len` of its characters are consumed. A
+    /// semicolon keeps itself as the value, which tells it apart from the end of a line as both
+    /// are `EndOfLine` tokens
+    fn char_token(&self, char: char, token: TokenType, len: u8) -> Token {
+        // Every character after the first of a longer token is ASCII, e.g. the `=` of `==`
+        let extra = len as usize - 1;
+        let start = Position::with_offset(self.line, self.col - extra, self.offset - extra);
+
+        let value = (char == ';').then(|| TokenLiteral::String(char.to_string()));
+        Token::from_value_pos(token, start, self.get_pos_offset(1), value)
+    }
+
     fn match_char(&mut self, char: char) -> Option<(TokenType, u8)> {
         macro_rules! accept_eq_ret {
             ($sym_b:literal, $tru:expr, $fal:expr) => {
@@ -320,6 +510,7 @@ impl Lexer {
             '/' | '÷' => accept_eq_ret!('=', TokenType::DivideAssign, TokenType::Divide),
             '%' => accept_eq_ret!('=', TokenType::ModuloAssign, TokenType::Modulo),
             '^' => accept_eq_ret!('=', TokenType::PowerAssign, TokenType::Power),
+            '=' if self.accept_eq('>') => (TokenType::FatArrow, 2),
             '=' => accept_eq_ret!('=', TokenType::Equal, TokenType::Assign),
     
             '<' => accept_eq_ret!('=', TokenType::LesserThanEqual, TokenType::LesserThan),
@@ -327,24 +518,316 @@ impl Lexer {
             
             '!' => accept_eq_ret!('=', TokenType::NotEqual, TokenType::Not),
     
-            '&' => {
-                if self.accept_eq('&') {
-                    (TokenType::And, 2)
-                } else {
-                    return None
-                }
-            }
-    
-            '|' => {
-                if self.accept_eq('|') {
-                    (TokenType::Or, 2)
-                } else {
-                    return None
-                }
-            }
+            '&' if self.accept_eq('&') => (TokenType::And, 2),
+            '|' if self.accept_eq('|') => (TokenType::Or, 2),
+            '|' if self.accept_eq('>') => (TokenType::Pipe, 2),
     
+            '.' if self.chars.iter().take(2).eq(&['.', '.']) => {
+                self.pos_advance(2);
+                self.chars.drain(..2);
+                (TokenType::Spread, 3)
+            },
+            // A dot followed by a digit belongs to a float, e.g. `1.5` or `.5`
+            '.' if !self.chars.front().is_some_and(char::is_ascii_digit) => (TokenType::Dot, 1),
+
             ';' => (TokenType::EndOfLine, 1),
             _ => return None
         })
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+
+    use crate::{testing::tokenize, utils::colors::ColorChoice};
+
+    use super::*;
+
+    #[test]
+    fn dump_lists_one_row_per_token() {
+        let dump = token::dump(&tokenize("x = 1;\n"), &ColorChoice::Never.palette());
+
+        assert_eq!(dump, [
+            "TYPE       VALUE  LINE  COL",
+            "Symbol     x      1     1",
+            "Assign            1     3",
+            "Integer    1      1     5",
+            "EndOfLine  ;      1     6",
+            "EndOfFile         2     1",
+            "",
+        ].join("\n"));
+    }
+
+    #[test]
+    fn token_after_multi_line_string_is_on_the_right_line() {
+        let tokens = tokenize("x = \"one\ntwo\"; y");
+        let y = tokens.iter().find(|token| token.value == Some(TokenLiteral::String(String::from("y")))).unwrap();
+
+        assert_eq!(y.start.to_tuple(), (2, 7));
+    }
+
+    fn indentation_warnings(input: &str) -> Vec<Warning> {
+        let mut lexer = Lexer::from(input);
+        lexer.options.warn_mixed_indentation = true;
+        lexer.tokenize().unwrap();
+
+        lexer.warnings
+    }
+
+    #[test]
+    fn mixed_indentation_warns() {
+        let warnings = indentation_warnings("if true {\n\t    x = 1\n}");
+        assert!(matches!(warnings.as_slice(), [Warning::MixedIndentation { pos }] if pos.line == 2), "{:?}", warnings);
+    }
+
+    #[test]
+    fn consistent_indentation_does_not_warn() {
+        assert!(indentation_warnings("if true {\n\t\tx = 1\n    y = 2\n}").is_empty());
+    }
+
+    fn out_of_range(input: &str) -> (String, TokenType) {
+        let error = Lexer::from(input).tokenize().unwrap_err();
+
+        match error.downcast_ref() {
+            Some(LexerError::NumberOutOfRange { literal, expected, .. }) => (literal.to_owned(), expected.to_owned()),
+            _ => panic!("expected an out of range error, got {}", error),
+        }
+    }
+
+    #[test]
+    fn overflowing_integer_literal_errors() {
+        assert_eq!(out_of_range("x = 99999999999999999999"), (String::from("99999999999999999999"), TokenType::Integer));
+    }
+
+    #[test]
+    fn overflowing_float_literal_errors() {
+        let literal = format!("1{}.0", "0".repeat(40));
+        assert_eq!(out_of_range(&literal), (literal, TokenType::Float));
+    }
+
+    #[test]
+    fn booleans_are_lexed_as_literals() {
+        let tokens = tokenize("true false truthy");
+        let lexed: Vec<(TokenType, Option<TokenLiteral>)> = tokens.into_iter()
+            .map(|token| (token.token_type, token.value))
+            .collect();
+
+        assert_eq!(lexed, vec![
+            (TokenType::Boolean, Some(TokenLiteral::Boolean(true))),
+            (TokenType::Boolean, Some(TokenLiteral::Boolean(false))),
+            (TokenType::Symbol, Some(TokenLiteral::String(String::from("truthy")))),
+            (TokenType::EndOfLine, None),
+            (TokenType::EndOfFile, None),
+        ]);
+    }
+
+    #[test]
+    fn lexer_accepts_any_string_type() {
+        let owned = String::from("x = 1");
+
+        let from_str = Lexer::from("x = 1").tokenize().unwrap().to_owned();
+        let from_ref = Lexer::from(&owned).tokenize().unwrap().to_owned();
+        let from_owned = Lexer::from(owned).tokenize().unwrap().to_owned();
+
+        assert_eq!(from_str, from_ref);
+        assert_eq!(from_str, from_owned);
+    }
+
+    #[test]
+    fn tokens_map_to_categories() {
+        use token::TokenCategory;
+
+        let categories: Vec<TokenCategory> = tokenize("if x == 1 {").iter()
+            .map(|token| token.token_type.category())
+            .collect();
+
+        assert_eq!(categories, vec![
+            TokenCategory::Keyword,
+            TokenCategory::Identifier,
+            TokenCategory::Operator,
+            TokenCategory::Literal,
+            TokenCategory::Punctuation,
+            TokenCategory::Punctuation,
+            TokenCategory::Other,
+        ]);
+    }
+
+    fn string_value(input: &str) -> String {
+        match &tokenize(input)[0].value {
+            Some(TokenLiteral::String(value)) => value.to_owned(),
+            value => panic!("expected a string, got {:?}", value),
+        }
+    }
+
+    #[test]
+    fn unicode_escape_decodes() {
+        assert_eq!(string_value(r#""smile \u{1F600}""#), "smile \u{1F600}");
+    }
+
+    #[test]
+    fn hex_escape_decodes() {
+        assert_eq!(string_value(r#""\x41\x62""#), "Ab");
+    }
+
+    #[test]
+    fn out_of_range_code_point_errors() {
+        let error = Lexer::from(r#"x = "\u{110000}""#).tokenize().unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(LexerError::InvalidEscape { pos, .. }) if pos.col == 6), "{}", error);
+    }
+
+    #[test]
+    fn positions_order_by_line_then_column() {
+        assert!(Position::from(1, 9) < Position::from(2, 1));
+        assert!(Position::from(2, 1) < Position::from(2, 3));
+    }
+
+    #[test]
+    fn advancing_past_a_newline_starts_the_next_line() {
+        let mut pos = Position::from(1, 4);
+
+        pos.advance('a');
+        assert_eq!(pos.to_tuple(), (1, 5));
+
+        pos.advance('\n');
+        assert_eq!(pos.to_tuple(), (2, 0));
+    }
+
+    /// Tokenizes `input`, failing the test with the input if the lexer panics
+    fn tokenize_without_panicking(input: &str) {
+        let result = std::panic::catch_unwind(|| Lexer::from(input).tokenize().map(|_| ()));
+        assert!(result.is_ok(), "lexer panicked on {:?}", input);
+    }
+
+    #[test]
+    fn random_bytes_never_panic() {
+        // A fixed xorshift seed keeps failures reproducible without a proptest dependency
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        // Bytes drawn from the token alphabet hit deeper paths than uniformly random ones
+        let alphabet = b"0123456789.xeE_abcz \t\n\r\"'\\{}()[],;:#=+-*/%^<>!&|?u";
+
+        for _ in 0..2000 {
+            let len = (next() % 48) as usize;
+            let bytes: Vec<u8> = (0..len)
+                .map(|_| match next() % 4 {
+                    0 => next() as u8,
+                    _ => alphabet[(next() % alphabet.len() as u64) as usize],
+                })
+                .collect();
+
+            tokenize_without_panicking(&String::from_utf8_lossy(&bytes));
+        }
+    }
+
+    #[test]
+    fn pathological_inputs_never_panic() {
+        tokenize_without_panicking(&"9".repeat(100_000));
+        tokenize_without_panicking(&format!("1.{}", "9".repeat(100_000)));
+        tokenize_without_panicking(&"a".repeat(1_000_000));
+        tokenize_without_panicking(&"\"".repeat(10_001));
+        tokenize_without_panicking(&"#".repeat(10_000));
+        tokenize_without_panicking("\"\\u{d800}\"");
+        tokenize_without_panicking("\"\\u{");
+        tokenize_without_panicking("\"\\x");
+        tokenize_without_panicking("\"\\");
+    }
+
+    fn starts(input: &str) -> Vec<(TokenType, (usize, usize))> {
+        tokenize(input).iter().map(|token| (token.token_type.clone(), token.start.to_tuple())).collect()
+    }
+
+    #[test]
+    fn positions_are_one_based() {
+        assert_eq!(starts("a\nbc # note\n"), [
+            (TokenType::Symbol, (1, 1)),
+            (TokenType::EndOfLine, (1, 2)),
+            (TokenType::Symbol, (2, 1)),
+            (TokenType::EndOfLine, (2, 10)),
+            (TokenType::EndOfFile, (3, 1)),
+        ]);
+    }
+
+    #[test]
+    fn end_of_file_points_past_the_last_character() {
+        assert_eq!(starts("ab").last(), Some(&(TokenType::EndOfFile, (1, 3))));
+    }
+
+    fn words(input: &str) -> Vec<(TokenType, Option<TokenLiteral>)> {
+        tokenize(input).into_iter()
+            .filter(|token| !matches!(token.token_type, TokenType::EndOfLine | TokenType::EndOfFile))
+            .map(|token| (token.token_type, token.value))
+            .collect()
+    }
+
+    fn symbol(name: &str) -> (TokenType, Option<TokenLiteral>) {
+        (TokenType::Symbol, Some(TokenLiteral::String(String::from(name))))
+    }
+
+    #[test]
+    fn identifiers_take_letters_digits_and_underscores() {
+        assert_eq!(words("_x x1 camelCase123 __"), [symbol("_x"), symbol("x1"), symbol("camelCase123"), symbol("__")]);
+    }
+
+    #[test]
+    fn leading_digit_starts_a_number() {
+        assert_eq!(words("1x"), [(TokenType::Integer, Some(TokenLiteral::Integer(1))), symbol("x")]);
+        assert_eq!(words("1e3"), [(TokenType::Float, Some(TokenLiteral::Float(1000.0)))]);
+    }
+
+    #[test]
+    fn invalid_identifier_characters_error() {
+        let error = Lexer::from("a$b").tokenize().unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(LexerError::InvalidCharacter { character: '$', .. })), "{}", error);
+
+        let error = Lexer::from("1.2.3").tokenize().unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(LexerError::InvalidNumber { .. })), "{}", error);
+    }
+
+    #[test]
+    fn tokens_on_the_second_line_have_byte_offsets() {
+        let tokens = tokenize("x = 1\ny == \"é\" |> f");
+        let second_line: Vec<(usize, Range<usize>)> = tokens.iter()
+            .filter(|token| token.start.line == 2 && !matches!(token.token_type, TokenType::EndOfLine | TokenType::EndOfFile))
+            .map(|token| (token.start.col, token.span()))
+            .collect();
+
+        assert_eq!(second_line, [(1, 6..7), (3, 8..10), (6, 11..15), (10, 16..18), (13, 19..20)]);
+    }
+
+    #[test]
+    fn spans_slice_each_token_from_the_source() {
+        let source = "größe = 1\n\tif größe >= 1 {\n\t\tprintln(\"ok…\", größe...)\n}";
+        let texts: Vec<&str> = tokenize(source).iter()
+            .filter(|token| !matches!(token.token_type, TokenType::EndOfLine | TokenType::EndOfFile))
+            .map(|token| &source[token.span()])
+            .collect();
+
+        assert_eq!(texts, [
+            "größe", "=", "1",
+            "if", "größe", ">=", "1", "{",
+            "println", "(", "\"ok…\"", ",", "größe", "...", ")",
+            "}",
+        ]);
+    }
+
+    #[test]
+    fn longer_operators_directly_after_a_word_stay_whole() {
+        let types: Vec<TokenType> = words("x==1 a&&b a||b a|>f x+=1 r...").into_iter().map(|(token_type, _)| token_type).collect();
+
+        assert_eq!(types, [
+            TokenType::Symbol, TokenType::Equal, TokenType::Integer,
+            TokenType::Symbol, TokenType::And, TokenType::Symbol,
+            TokenType::Symbol, TokenType::Or, TokenType::Symbol,
+            TokenType::Symbol, TokenType::Pipe, TokenType::Symbol,
+            TokenType::Symbol, TokenType::PlusAssign, TokenType::Integer,
+            TokenType::Symbol, TokenType::Spread,
+        ]);
+    }
+}
@@ -1,9 +1,14 @@
-use std::fmt::Display;
+use std::{fmt::Display, ops::Range};
 
-#[derive(Debug, Clone, PartialEq)]
+use crate::utils::colors::Palette;
+
+/// Ordered by line, then column
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Position {
     pub line: usize,
     pub col: usize,
+    /// Byte offset into the source, e.g. for editors which address text by bytes
+    pub offset: usize,
 }
 
 impl Display for Position {
@@ -14,18 +19,38 @@ impl Display for Position {
 
 impl Position {
     pub fn from(line: usize, col: usize) -> Self {
-        Self { line, col }
+        Self { line, col, offset: 0 }
+    }
+
+    pub fn with_offset(line: usize, col: usize, offset: usize) -> Self {
+        Self { line, col, offset }
+    }
+
+    /// Moves along the line by `cols` characters, each taken to be one byte long
+    pub fn shifted(&self, cols: usize) -> Self {
+        Self::with_offset(self.line, self.col + cols, self.offset + cols)
     }
 
     pub fn to_tuple(&self) -> (usize, usize) {
         (self.line, self.col)
     }
+
+    /// Moves past a character, a newline starting the next line
+    pub fn advance(&mut self, char: char) {
+        if char == '\n' {
+            self.line += 1;
+            self.col = 0;
+        } else {
+            self.col += 1;
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum TokenLiteral {
     Float(f32),
-    Integer(i32),
+    /// The sign is a separate token, so this is only ever the magnitude
+    Integer(u32),
     Boolean(bool),
     String(String),
 }
@@ -63,6 +88,11 @@ impl Token {
         Token::from_value_pos(token, start, end, None)
     }
 
+    /// The bytes of the source which the token covers
+    pub fn span(&self) -> Range<usize> {
+        self.start.offset..self.end.offset
+    }
+
     pub fn from_value(token: TokenType, value: Option<TokenLiteral>) -> Self {
         Self::from_value_pos(token, Position::from(0, 0), Position::from(0, 0), value)
     }
@@ -78,6 +108,28 @@ impl Token {
 
 pub type Tokens = Vec<Token>;
 
+/// Formats tokens as a table with one row per token: type, value, line and column
+pub fn dump(tokens: &[Token], palette: &Palette) -> String {
+    let rows: Vec<(String, String, String, String)> = tokens.iter().map(|token| (
+        format!("{:?}", token.token_type),
+        token.value.as_ref().map(|value| value.get_value().escape_debug().to_string()).unwrap_or_default(),
+        token.start.line.to_string(),
+        token.start.col.to_string(),
+    )).collect();
+
+    let type_width = rows.iter().map(|row| row.0.len()).max().unwrap_or(0).max(4);
+    let value_width = rows.iter().map(|row| row.1.len()).max().unwrap_or(0).max(5);
+
+    let mut output = format!("{:<type_width$}  {:<value_width$}  LINE  COL\n", "TYPE", "VALUE");
+    for (token, (token_type, value, line, col)) in tokens.iter().zip(rows) {
+        let color = token.token_type.category().color(palette);
+        let reset = palette.reset;
+        output.push_str(&format!("{color}{:<type_width$}{reset}  {:<value_width$}  {:<4}  {}\n", token_type, value, line, col));
+    }
+
+    output
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum TokenType {
     // Types
@@ -113,6 +165,8 @@ pub enum TokenType {
     GreaterThan,
     LesserThanEqual,
     GreaterThanEqual,
+    In,
+    Pipe,
     // EOF Operators
 
     // keywords
@@ -124,6 +178,10 @@ pub enum TokenType {
     Return,
     Break,
     Continue,
+    Match,
+    Throw,
+    Try,
+    Catch,
 
     // Other
     LeftParen,
@@ -136,6 +194,85 @@ pub enum TokenType {
     EndOfFile,
     Symbol,
     Comma,
+    Spread,
+    FatArrow,
+    Dot,
 
     Unknown
-}
\ No newline at end of file
+}
+
+/// Broad classification of token types, meant for syntax highlighting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenCategory {
+    Keyword,
+    Operator,
+    Literal,
+    Identifier,
+    Punctuation,
+    Other,
+}
+
+impl TokenCategory {
+    pub fn color(&self, palette: &Palette) -> &'static str {
+        match self {
+            TokenCategory::Keyword => palette.magenta,
+            TokenCategory::Operator => palette.yellow,
+            TokenCategory::Literal => palette.green,
+            TokenCategory::Identifier => palette.cyan,
+            TokenCategory::Punctuation => palette.blue,
+            TokenCategory::Other => palette.reset,
+        }
+    }
+}
+
+impl TokenType {
+    /// Whether a statement can end with this token, in which case a following newline terminates it
+    pub fn ends_statement(&self) -> bool {
+        matches!(self,
+            TokenType::Integer | TokenType::Float | TokenType::Boolean | TokenType::String
+            | TokenType::Null | TokenType::Symbol | TokenType::RightParen | TokenType::RightBracket
+            | TokenType::RightBrace | TokenType::Return | TokenType::Break | TokenType::Continue
+        )
+    }
+
+    /// Whether a line starting with this token continues the statement on the line before, as it
+    /// can't start an expression. `-` and `!` can, so a line starting with one of them doesn't
+    pub fn continues_line(&self) -> bool {
+        matches!(self,
+            TokenType::Dot | TokenType::Pipe | TokenType::Or | TokenType::And | TokenType::Equal
+            | TokenType::NotEqual | TokenType::LesserThan | TokenType::GreaterThan
+            | TokenType::LesserThanEqual | TokenType::GreaterThanEqual | TokenType::In
+            | TokenType::Plus | TokenType::Multiply | TokenType::Divide | TokenType::Modulo
+            | TokenType::Power
+        )
+    }
+
+    pub fn category(&self) -> TokenCategory {
+        match self {
+            TokenType::Integer | TokenType::Float | TokenType::Boolean
+                | TokenType::String | TokenType::List | TokenType::Null => TokenCategory::Literal,
+
+            TokenType::Plus | TokenType::Minus | TokenType::Divide | TokenType::Multiply
+                | TokenType::Modulo | TokenType::Power | TokenType::PlusAssign
+                | TokenType::MinusAssign | TokenType::DivideAssign | TokenType::MultiplyAssign
+                | TokenType::ModuloAssign | TokenType::PowerAssign | TokenType::Assign
+                | TokenType::Or | TokenType::And | TokenType::Not | TokenType::Equal
+                | TokenType::NotEqual | TokenType::LesserThan | TokenType::GreaterThan
+                | TokenType::LesserThanEqual | TokenType::GreaterThanEqual
+                | TokenType::Pipe | TokenType::Spread | TokenType::FatArrow => TokenCategory::Operator,
+
+            TokenType::If | TokenType::ElIf | TokenType::Else | TokenType::While
+                | TokenType::For | TokenType::Return | TokenType::Break
+                | TokenType::Continue | TokenType::Match | TokenType::In
+                | TokenType::Throw | TokenType::Try | TokenType::Catch => TokenCategory::Keyword,
+
+            TokenType::LeftParen | TokenType::LeftBrace | TokenType::LeftBracket
+                | TokenType::RightParen | TokenType::RightBrace | TokenType::RightBracket
+                | TokenType::Comma | TokenType::Dot | TokenType::EndOfLine => TokenCategory::Punctuation,
+
+            TokenType::Symbol => TokenCategory::Identifier,
+
+            TokenType::EndOfFile | TokenType::Unknown => TokenCategory::Other,
+        }
+    }
+}
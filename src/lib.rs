@@ -1,5 +1,9 @@
 pub mod lexer;
 pub mod parser;
 pub mod evaluator;
+pub mod analyzer;
 pub mod utils;
-pub mod errors;
\ No newline at end of file
+pub mod errors;
+
+#[cfg(test)]
+mod testing;
@@ -1,125 +1,736 @@
-use std::{fs, io::{stdin, stdout, Write}, process::exit};
+use std::{error::Error, fmt::Display, fs, io::{self, stdin, stdout, BufRead, Write}, process::exit, time::{Duration, Instant}};
 
-use another_interpreted_language::{error, errors::DynamicError, evaluator::{object::{Object, ObjectType}, Evaluator}, lexer::{token::Tokens, Lexer}, parser::{ast::Node, Parser}, utils::colors::{BLUE, BOLD, CYAN, GREEN, MAGENTA, RED, RESET, UNDERLINE, YELLOW}};
+use another_interpreted_language::{analyzer::{Analyzer, AnalyzerOptions}, error, errors::{error_position, exit_code, failure_code, DynamicError, Warning, EXIT_SYNTAX_ERROR}, evaluator::{environment::Environment, object::{Object, ObjectType}, yaipl_std, Evaluator}, lexer::{token::{self, Position, Tokens}, Lexer, LexerOptions}, parser::{ast::Node, Parser}, utils::colors::{ColorChoice, Palette}};
 
 pub const NAME: &str = "YAIPL";
 pub const NAME_LONG: &str = "Yet Another Interpreted Programming Language";
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// How long each phase of `interpret` took
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timings {
+    pub lexer: Duration,
+    pub parser: Duration,
+    pub evaluator: Duration,
+}
+
+impl Timings {
+    pub fn total(&self) -> Duration {
+        self.lexer + self.parser + self.evaluator
+    }
+
+    /// Formats the timings in microseconds as a single line JSON object, for benchmarking scripts
+    pub fn to_json(&self) -> String {
+        format!("{{\"lexer_us\":{},\"parser_us\":{},\"eval_us\":{}}}",
+            self.lexer.as_micros(),
+            self.parser.as_micros(),
+            self.evaluator.as_micros()
+        )
+    }
+
+    /// Formats the timings on a single line for people to read, with the phase names in bold
+    pub fn pretty(&self, palette: &Palette) -> String {
+        let Palette { bold, reset, .. } = palette;
+
+        format!("{}Lexer{} {:?} {}Parser{} {:?} {}Evaluator{} {:?} {}Total{} {:?}",
+            bold, reset, self.lexer,
+            bold, reset, self.parser,
+            bold, reset, self.evaluator,
+            bold, reset, self.total()
+        )
+    }
+}
+
+/// What `parse_file` prints after running a file, besides its result and warnings
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ShowOptions {
+    pub timings: bool,
+    pub timings_json: bool,
+    pub tokens: bool,
+    pub ast: bool,
+}
+
+/// The input file couldn't be read. Returned rather than exiting, so `main` decides how to end the process
+#[derive(Debug)]
+pub struct ReadError(pub io::Error);
+
+impl Error for ReadError {}
+impl Display for ReadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Could not read file: {}", self.0)
+    }
+}
+
+/// Every error found by parsing a file again with recovery, along with its source for showing snippets.
+/// Returned rather than printed, so `main` decides how to report them and end the process
+#[derive(Debug)]
+pub struct SyntaxErrors {
+    pub errors: Vec<DynamicError>,
+    pub source: String,
+}
+
+impl Error for SyntaxErrors {}
+impl Display for SyntaxErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Found {} syntax errors", self.errors.len())
+    }
+}
+
 fn main() {
-    let args: Vec<String> = std::env::args().collect();
+    let mut flags = Vec::new();
+    let mut args = Vec::new();
+    let mut prelude = None;
 
-    if args.len() < 2 {
-        // No input file provided, run REPL
-        repl();
-        exit(1);
+    let mut raw_args = std::env::args();
+    while let Some(arg) = raw_args.next() {
+        // `--prelude` takes a value, either as `--prelude=file` or as the next argument
+        if let Some(path) = arg.strip_prefix("--prelude=") {
+            prelude = Some(path.to_string());
+        } else if arg == "--prelude" {
+            match raw_args.next() {
+                Some(path) => prelude = Some(path),
+                None => {
+                    println!("Missing value for --prelude, expected a file");
+                    exit(1)
+                }
+            }
+        } else if arg.starts_with("--") {
+            flags.push(arg);
+        } else {
+            args.push(arg);
+        }
     }
 
-    // Input file provided
-    let file_path = &args[1];
+    let show = ShowOptions {
+        timings: flags.iter().any(|flag| flag == "--timings"),
+        timings_json: flags.iter().any(|flag| flag == "--emit-timings-json"),
+        tokens: flags.iter().any(|flag| flag == "--tokens"),
+        ast: flags.iter().any(|flag| flag == "--ast"),
+    };
+    let check_only = flags.iter().any(|flag| flag == "--check");
+    let lexer_options = LexerOptions {
+        warn_mixed_indentation: flags.iter().any(|flag| flag == "--warn-mixed-indent"),
+    };
+    let analyzer_options = AnalyzerOptions {
+        warn_shadowing: flags.iter().any(|flag| flag == "--warn-shadowing"),
+    };
+
+    let mut color = ColorChoice::default();
+    for value in flags.iter().filter_map(|flag| flag.strip_prefix("--color=")) {
+        color = match ColorChoice::from(value) {
+            Some(color) => color,
+            None => {
+                println!("Invalid value for --color: '{}', expected auto, always or never", value);
+                exit(1)
+            }
+        };
+    }
+    let palette = color.palette();
+
+    // There is nothing to check in the REPL, so checking needs at least one file
+    if check_only && args.len() < 2 {
+        println!("Usage: yaipl --check <file>...");
+        exit(1)
+    }
+
+    // Files run in order in one environment, so earlier files can define helpers for later ones,
+    // and the first error stops the rest from running. The prelude runs before the input files,
+    // or before the REPL starts when there are none
     let cwd = std::env::current_dir().expect("Could not get current directory");
-    let absolute_path = match fs::canonicalize(file_path) {
-        Ok(path) => path,
-        Err(err) => {
-            println!("Could not get absolute path: {}", err);
-            exit(1)
+    let mut env = Environment::new();
+    yaipl_std::initialize(&mut env);
+
+    for file_path in prelude.iter().chain(&args[1..]) {
+        let absolute_path = match fs::canonicalize(file_path) {
+            Ok(path) => path,
+            Err(err) => {
+                println!("Could not get absolute path: {}", err);
+                exit(1)
+            }
+        };
+
+        let pretty_path = match absolute_path.strip_prefix(&cwd) {
+            Ok(path) => path.display().to_string(),
+            Err(_) => absolute_path.display().to_string()
+        };
+
+        if check_only {
+            if let Err(err) = check_file(&pretty_path, lexer_options.to_owned(), analyzer_options.to_owned(), &palette) {
+                exit_on_read_error(&err);
+                let code = failure_code(&err);
+                handle_errors(err, Some(pretty_path), &palette);
+                exit(code);
+            }
+
+            continue;
         }
-    };
 
-    let pretty_path = match absolute_path.strip_prefix(&cwd) {
-        Ok(path) => path.display().to_string(),
-        Err(_) => absolute_path.display().to_string()
-    };
+        if let Err(err) = parse_file(&pretty_path, lexer_options.to_owned(), analyzer_options.to_owned(), &palette, show, &mut env) {
+            if let Some(code) = exit_code(&err) {
+                exit(code);
+            }
+
+            let err = match err.downcast::<SyntaxErrors>() {
+                Ok(errors) => {
+                    handle_syntax_errors(*errors, Some(pretty_path), &palette);
+                    exit(EXIT_SYNTAX_ERROR);
+                },
+                Err(err) => err,
+            };
+
+            exit_on_read_error(&err);
+            let code = failure_code(&err);
+            handle_errors(err, Some(pretty_path), &palette);
+            exit(code);
+        }
+    }
+
+    if args.len() < 2 {
+        // No input file provided, run REPL
+        exit(repl(&palette, env, &mut stdin().lock()));
+    }
+}
 
-    if let Err(err) = parse_file(file_path) {
-        handle_errors(err, Some(pretty_path));
+fn exit_on_read_error(err: &DynamicError) {
+    if let Some(err) = err.downcast_ref::<ReadError>() {
+        println!("{}", err);
+        exit(1);
     }
 }
 
-pub fn repl() {
-    println!("{}{}{} - {}REPL Mode{}", 
-        format!("{}{}{}", GREEN, BOLD, UNDERLINE),
+/// Runs the REPL in `env` on lines read from `input` until `exit` is called or the input ends,
+/// returning its exit code. Bindings made on one line stay available on the next
+pub fn repl(palette: &Palette, mut env: Environment, input: &mut impl BufRead) -> i32 {
+    let Palette { red, green, blue, reset, bold, underline, .. } = palette;
+
+    println!("{}{}{}{}{} - {}{}REPL Mode{}", 
+        green, bold, underline,
         NAME,
-        RESET,
-        format!("{}{}", BLUE, BOLD), 
-        RESET
+        reset,
+        blue, bold,
+        reset
     );
     
-    let stdin = stdin();
     let mut buf = String::new();
 
     loop {
-        print!("\n{}{}>>>{} ", BOLD, BLUE, RESET);
+        print!("\n{}{}>>>{} ", bold, blue, reset);
         let _ = stdout().flush();
-        let _ = stdin.read_line(&mut buf);
+        match input.read_line(&mut buf) {
+            // End of input (e.g. Ctrl+D or a closed pipe) ends the session
+            Ok(0) => return 0,
+            Ok(_) => {},
+            Err(err) => {
+                println!("{}Could not read input{}: {}", red, reset, err);
+                return 1;
+            }
+        }
 
         if !buf.ends_with(';') {
             buf.push(';');
         }
 
-        let (_, _, result) = match interpret(buf.to_owned()) {
+        let result = match run_only(&buf, LexerOptions::default(), &mut env) {
             Ok(res) => res,
             Err(err) => {
-                handle_errors(err, None);
+                if let Some(code) = exit_code(&err) {
+                    return code;
+                }
+
+                handle_errors(err, None, palette);
                 buf.clear();
                 continue;
             }
         };
 
-        println!("{}", result.to_string_with_type());
+        if let Some(result) = format_result(&result, palette) {
+            println!("{}", result);
+        }
+
+        // The last value shown is kept as `it`, e.g. `2 + 3` followed by `it * 2`
+        if !result.is(ObjectType::Void) {
+            env.set("it", result);
+        }
+
+        // Bindings stay in `env`, so the line doesn't have to run again along with the next one
+        buf.clear();
     }
 }
 
-fn interpret(input: String) -> Result<(Tokens, Vec<Node>, Object), DynamicError> {
+/// Colors a REPL result by its type, returning `None` for `Void` as there is nothing to show
+fn format_result(result: &Object, palette: &Palette) -> Option<String> {
+    let color = match result.get_type() {
+        ObjectType::Void => return None,
+        ObjectType::String => return Some(format!("{}\"{}\"{}", palette.green, result, palette.reset)),
+        ObjectType::Integer | ObjectType::Float => palette.cyan,
+        ObjectType::Boolean => palette.magenta,
+        ObjectType::Function | ObjectType::NativeFunction => palette.blue,
+        ObjectType::Null | ObjectType::List => palette.bold,
+    };
+
+    Some(format!("{}{}{}", color, result, palette.reset))
+}
+
+type InterpretResult = Result<(Tokens, Vec<Node>, Object, Vec<Warning>, Timings), DynamicError>;
+
+/// Runs `input` in `env`, which keeps the bindings it makes
+fn interpret(input: String, lexer_options: LexerOptions, analyzer_options: AnalyzerOptions, env: &mut Environment) -> InterpretResult {
+    let mut timings = Timings::default();
+
+    let start = Instant::now();
     let mut lexer = Lexer::from(&input);
-    let tokens = lexer.tokenize()?;
+    lexer.options = lexer_options;
+    let tokens = lexer.tokenize()?.to_owned();
+    let mut warnings = lexer.warnings;
+    timings.lexer = start.elapsed();
 
+    let start = Instant::now();
     let mut parser = Parser::from(&tokens);
     let ast = parser.parse()?;
+    timings.parser = start.elapsed();
 
     if let Node::Program(ast) = ast {
-        let mut evaluator = Evaluator::new(&ast);
-        let result = evaluator.eval()?;
+        let mut analyzer = Analyzer::with_env(&ast, env);
+        analyzer.options = analyzer_options;
+        warnings.extend(analyzer.analyze()?);
+
+        let start = Instant::now();
+        let mut evaluator = Evaluator::with_env(&ast, std::mem::take(env));
+        let result = evaluator.eval();
+        *env = evaluator.into_env();
+        let result = result?;
+        timings.evaluator = start.elapsed();
+
+        return Ok((tokens, ast, result, warnings, timings));
+    }
+
+    error!("AST is not a program node.");
+}
+
+/// Like `interpret`, but only returns the result and evaluates in `env`, so its bindings outlive
+/// the call. The tokens are borrowed from the lexer rather than cloned, and the analyzer is
+/// skipped as nobody would see its warnings
+fn run_only(input: &str, lexer_options: LexerOptions, env: &mut Environment) -> Result<Object, DynamicError> {
+    let mut lexer = Lexer::from(input);
+    lexer.options = lexer_options;
+    let tokens = lexer.tokenize()?;
+
+    if let Node::Program(ast) = Parser::from(tokens).parse()? {
+        let size = env.size();
+        let mut evaluator = Evaluator::with_env(&ast, std::mem::take(env));
+        let result = evaluator.eval();
+        *env = evaluator.into_env();
+
+        // A failed line may leave the locals of the scope it failed in behind
+        if result.is_err() {
+            env.truncate(size);
+        }
 
-        return Ok((tokens.to_owned(), ast, result));
+        return result;
     }
 
     error!("AST is not a program node.");
 }
 
-pub fn parse_file(path: &String) -> Result<(), DynamicError> {
-    let content = match fs::read_to_string(path) {
-        Ok(text) => text,
+fn read_file(path: &String) -> Result<String, ReadError> {
+    fs::read_to_string(path).map_err(ReadError)
+}
+
+/// Lexes, parses and analyzes a file without evaluating it, so none of its side effects run
+pub fn check_file(path: &String, lexer_options: LexerOptions, analyzer_options: AnalyzerOptions, palette: &Palette) -> Result<(), DynamicError> {
+    let content = read_file(path)?;
+
+    let mut lexer = Lexer::from(&content);
+    lexer.options = lexer_options;
+    let tokens = lexer.tokenize()?.to_owned();
+    let mut warnings = lexer.warnings;
+
+    if let Node::Program(ast) = Parser::from(&tokens).parse()? {
+        let mut analyzer = Analyzer::from(&ast);
+        analyzer.options = analyzer_options;
+        warnings.extend(analyzer.analyze()?);
+    }
+
+    handle_warnings(&warnings, Some(path.to_owned()), palette);
+    Ok(())
+}
+
+pub fn parse_file(path: &String, lexer_options: LexerOptions, analyzer_options: AnalyzerOptions, palette: &Palette, show: ShowOptions, env: &mut Environment) -> Result<(), DynamicError> {
+    let content = read_file(path)?;
+
+    let (tokens, ast, result, warnings, timings) = match interpret(content.to_owned(), lexer_options.to_owned(), analyzer_options, env) {
+        Ok(result) => result,
         Err(err) => {
-            println!("Could not read file: {}", err);
-            exit(1)
-        } 
-    };
+            // Parsing stops at the first error, so parse again recovering from each one to show them all at once
+            let errors = parse_errors(&content, lexer_options);
+            if errors.is_empty() {
+                return Err(err);
+            }
 
-    let (_, _, result) = interpret(content)?;
+            error!(SyntaxErrors { errors, source: content });
+        }
+    };
 
     if !result.is(ObjectType::Void) {
         println!("{}", result);
     }
 
+    handle_warnings(&warnings, Some(path.to_owned()), palette);
+
+    if show.tokens {
+        print!("{}", token::dump(&tokens, palette));
+    }
+
+    if show.ast {
+        ast.iter().for_each(|node| print!("{}", node.pretty()));
+    }
+
+    if show.timings_json {
+        println!("{}", timings.to_json());
+    } else if show.timings {
+        println!("{}", timings.pretty(palette));
+    }
+
     Ok(())
 }
 
-fn handle_errors(err: DynamicError, path: Option<String>) {
+/// Every parse error in `content`, which is empty if it can't be lexed or parses fine
+fn parse_errors(content: &str, lexer_options: LexerOptions) -> Vec<DynamicError> {
+    let mut lexer = Lexer::from(content);
+    lexer.options = lexer_options;
+
+    match lexer.tokenize() {
+        Ok(tokens) => Parser::from(tokens).parse_recovering().err().unwrap_or_default(),
+        Err(_) => Vec::new()
+    }
+}
+
+/// Shows the source line at `pos` with a caret under its column
+fn snippet(content: &str, pos: &Position, palette: &Palette) -> String {
+    let Some(line) = content.lines().nth(pos.line.saturating_sub(1)) else {
+        return String::new();
+    };
+
+    let number = pos.line.to_string();
+    let gutter = " ".repeat(number.len());
+    // Tabs are kept so the caret lines up however wide they are shown
+    let indent: String = line.chars()
+        .take(pos.col.saturating_sub(1))
+        .map(|char| if char == '\t' { '\t' } else { ' ' })
+        .collect();
+
+    format!("{gutter} |\n{number} | {line}\n{gutter} | {indent}{}^{}\n", palette.red, palette.reset)
+}
+
+fn handle_errors(err: DynamicError, path: Option<String>, palette: &Palette) {
     let name = format!("{:?}", err);
-    let name = name.split_once(' ').unwrap_or(("UnknownError", "")).0;
-    let as_str = err.to_string()
-        .replace(r"{{path}}", &path.unwrap_or("unknown_path".to_string()))
-        .replace("&r", RED)
-        .replace("&g", GREEN)
-        .replace("&b", BLUE)
-        .replace("&c", CYAN)
-        .replace("&m", MAGENTA)
-        .replace("&y", YELLOW)
-        .replace("&-", RESET)
-        .replace("&_", UNDERLINE)
-        .replace("&*", BOLD);
-
-    println!("{}{}{}{} was thrown: {}{}", BLUE, name, RESET, RED, as_str, RESET);
+    let name = match name.split(|c: char| !c.is_alphanumeric() && c != '_').next() {
+        Some(name) if !name.is_empty() => name,
+        _ => "UnknownError"
+    };
+    let as_str = format_message(err.to_string(), palette.red, &path, palette);
+
+    println!("{}{}{}{} was thrown: {}{}", palette.blue, name, palette.reset, palette.red, as_str, palette.reset);
+}
+
+/// Shows each error with a snippet of the source line it points at
+fn handle_syntax_errors(errors: SyntaxErrors, path: Option<String>, palette: &Palette) {
+    for error in errors.errors {
+        let pos = error_position(&error);
+        handle_errors(error, path.to_owned(), palette);
+
+        if let Some(pos) = pos {
+            print!("{}", snippet(&errors.source, &pos, palette));
+        }
+    }
+}
+
+fn handle_warnings(warnings: &[Warning], path: Option<String>, palette: &Palette) {
+    for warning in warnings {
+        let as_str = format_message(warning.to_string(), palette.yellow, &path, palette);
+
+        println!("{}Warning{}{}: {}{}", palette.bold, palette.reset, palette.yellow, as_str, palette.reset);
+    }
+}
+
+/// Replaces the color codes and path placeholder in a message, `&r` being the message's base color
+fn format_message(message: String, base: &str, path: &Option<String>, palette: &Palette) -> String {
+    message
+        .replace(r"{{path}}", path.as_deref().unwrap_or("unknown_path"))
+        .replace("&r", base)
+        .replace("&g", palette.green)
+        .replace("&b", palette.blue)
+        .replace("&c", palette.cyan)
+        .replace("&m", palette.magenta)
+        .replace("&y", palette.yellow)
+        .replace("&-", palette.reset)
+        .replace("&_", palette.underline)
+        .replace("&*", palette.bold)
 }
 
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use another_interpreted_language::errors::{EvaluatorError, EXIT_RUNTIME_ERROR};
+
+    use super::*;
+
+    /// Writes `content` to a file in the temp directory which is removed again when dropped
+    struct TempFile(String);
+
+    impl TempFile {
+        fn new(name: &str, content: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("yaipl-{}-{}", std::process::id(), name));
+            fs::write(&path, content).expect("Could not write temp file");
+            Self(path.display().to_string())
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    /// A fresh environment with the standard library, as the interpreter starts with
+    fn globals() -> Environment {
+        let mut env = Environment::new();
+        yaipl_std::initialize(&mut env);
+        env
+    }
+
+    fn run_file(path: &String) -> Result<(), DynamicError> {
+        parse_file(path, LexerOptions::default(), AnalyzerOptions::default(), &ColorChoice::Never.palette(), ShowOptions::default(), &mut globals())
+    }
+
+    #[test]
+    fn parse_file_collects_every_syntax_error() {
+        let file = TempFile::new("errors.yaipl", "x = 1 + ;\ny = 2;\nz = * 3;\n");
+        let err = run_file(&file.0).unwrap_err();
+
+        let errors = err.downcast::<SyntaxErrors>().expect("expected syntax errors");
+        let lines: Vec<usize> = errors.errors.iter()
+            .filter_map(error_position)
+            .map(|pos| pos.line)
+            .collect();
+
+        assert_eq!(lines, vec![1, 3]);
+    }
+
+    #[test]
+    fn check_file_does_not_evaluate() {
+        let file = TempFile::new("check.yaipl", "x = 1 / 0;\nthrow x;\n");
+        assert!(check_file(&file.0, LexerOptions::default(), AnalyzerOptions::default(), &ColorChoice::Never.palette()).is_ok());
+    }
+
+    #[test]
+    fn check_file_fails_on_syntax_error() {
+        let file = TempFile::new("check_invalid.yaipl", "x = 1 + ;\n");
+        let err = check_file(&file.0, LexerOptions::default(), AnalyzerOptions::default(), &ColorChoice::Never.palette()).unwrap_err();
+
+        assert_eq!(failure_code(&err), EXIT_SYNTAX_ERROR);
+    }
+
+    #[test]
+    fn parse_file_returns_read_error_for_missing_file() {
+        let path = std::env::temp_dir().join("yaipl-does-not-exist.yaipl").display().to_string();
+        let err = run_file(&path).unwrap_err();
+
+        assert!(err.is::<ReadError>());
+    }
+
+    #[test]
+    fn interpret_returns_every_timing() {
+        let (.., timings) = interpret(String::from("x = 1 + 2; x * 3;"), LexerOptions::default(), AnalyzerOptions::default(), &mut globals()).unwrap();
+
+        for phase in [timings.lexer, timings.parser, timings.evaluator] {
+            assert!(phase > Duration::ZERO);
+            assert!(timings.total() >= phase);
+        }
+    }
+
+    #[test]
+    fn results_are_colored_by_type() {
+        let palette = ColorChoice::Always.palette();
+
+        assert_eq!(format_result(&Object::integer(3), &palette).unwrap(), "\x1b[36m3\x1b[0m");
+        assert_eq!(format_result(&Object::float(1.5), &palette).unwrap(), "\x1b[36m1.5\x1b[0m");
+        assert_eq!(format_result(&Object::string("hi"), &palette).unwrap(), "\x1b[32m\"hi\"\x1b[0m");
+        assert_eq!(format_result(&Object::boolean(true), &palette).unwrap(), "\x1b[35mtrue\x1b[0m");
+        assert_eq!(format_result(&Object::null(), &palette).unwrap(), "\x1b[1mnull\x1b[0m");
+        assert_eq!(format_result(&Object::void(), &palette), None);
+    }
+
+    #[test]
+    fn repl_shows_nothing_for_assignments() {
+        let palette = ColorChoice::Never.palette();
+        let mut env = globals();
+
+        let assigned = run_only("x = 1;", LexerOptions::default(), &mut env).unwrap();
+        let sum = run_only("1+1;", LexerOptions::default(), &mut env).unwrap();
+
+        assert_eq!(format_result(&assigned, &palette), None);
+        assert_eq!(format_result(&sum, &palette).as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn repl_ends_when_input_is_closed() {
+        assert_eq!(repl(&ColorChoice::Never.palette(), globals(), &mut io::empty()), 0);
+    }
+
+    #[test]
+    fn repl_exits_with_the_code_from_exit() {
+        assert_eq!(repl(&ColorChoice::Never.palette(), globals(), &mut "x = 1\nexit(3)\n".as_bytes()), 3);
+    }
+
+    #[test]
+    fn repl_fails_when_input_errors() {
+        struct Broken;
+
+        impl io::Read for Broken {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("broken pipe"))
+            }
+        }
+
+        assert_eq!(repl(&ColorChoice::Never.palette(), globals(), &mut io::BufReader::new(Broken)), 1);
+    }
+
+    #[test]
+    fn timings_json_has_the_three_phases_in_microseconds() {
+        let timings = Timings {
+            lexer: Duration::from_micros(12),
+            parser: Duration::from_micros(345),
+            evaluator: Duration::from_millis(6),
+        };
+
+        assert_eq!(timings.to_json(), "{\"lexer_us\":12,\"parser_us\":345,\"eval_us\":6000}");
+    }
+
+    #[test]
+    fn run_only_matches_interpret() {
+        let inputs = ["x = 1 + 2; x * 3;", "join([\"a\", \"b\"], \"-\")", "f = (n) { n * 2; }; f(21)", "[1, 2.5, true]"];
+
+        for input in inputs {
+            let (_, _, full, ..) = interpret(String::from(input), LexerOptions::default(), AnalyzerOptions::default(), &mut globals()).unwrap();
+            let lean = run_only(input, LexerOptions::default(), &mut globals()).unwrap();
+
+            assert_eq!(full, lean, "{}", input);
+        }
+    }
+
+    #[test]
+    fn color_flag_values_select_a_choice() {
+        assert_eq!(ColorChoice::from("auto"), Some(ColorChoice::Auto));
+        assert_eq!(ColorChoice::from("always"), Some(ColorChoice::Always));
+        assert_eq!(ColorChoice::from("never"), Some(ColorChoice::Never));
+        assert_eq!(ColorChoice::from("sometimes"), None);
+    }
+
+    #[test]
+    fn never_prints_results_without_codes() {
+        let palette = ColorChoice::Never.palette();
+
+        assert_eq!(palette, Palette::none());
+        assert_eq!(format_result(&Object::integer(3), &palette).unwrap(), "3");
+        assert_eq!(format_result(&Object::string("hi"), &palette).unwrap(), "\"hi\"");
+    }
+
+    #[test]
+    fn always_uses_the_ansi_codes() {
+        assert_eq!(ColorChoice::Always.palette(), Palette::ansi());
+    }
+
+    fn repl_exit_code(input: &str) -> i32 {
+        repl(&ColorChoice::Never.palette(), globals(), &mut input.as_bytes())
+    }
+
+    #[test]
+    fn repl_binds_the_last_result_to_it() {
+        assert_eq!(repl_exit_code("2 + 3\nexit(it * 2)\n"), 10);
+    }
+
+    #[test]
+    fn repl_keeps_it_across_lines_without_a_value() {
+        assert_eq!(repl_exit_code("2 + 3\nx = 1\nexit(it)\n"), 5);
+    }
+
+    fn run_files(paths: &[&String], env: &mut Environment) -> Result<(), DynamicError> {
+        for path in paths {
+            parse_file(path, LexerOptions::default(), AnalyzerOptions::default(), &ColorChoice::Never.palette(), ShowOptions::default(), env)?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn later_files_use_functions_from_earlier_ones() {
+        let helpers = TempFile::new("helpers.yaipl", "double = (n) { n * 2; }\n");
+        let main = TempFile::new("uses-helpers.yaipl", "assert_eq(double(21), 42)\n");
+
+        run_files(&[&helpers.0, &main.0], &mut globals()).unwrap();
+    }
+
+    #[test]
+    fn files_run_alone_do_not_share_definitions() {
+        let main = TempFile::new("uses-missing-helpers.yaipl", "double(21)\n");
+        let error = run_file(&main.0).unwrap_err();
+
+        assert!(matches!(error.downcast_ref(), Some(EvaluatorError::ObjectNotFound { .. })), "{}", error);
+    }
+
+    #[test]
+    fn prelude_functions_are_callable_in_the_program() {
+        let prelude = TempFile::new("prelude.yaipl", "square = (n) { n * n; }\n");
+        let main = TempFile::new("uses-prelude.yaipl", "assert_eq(square(7), 49)\n");
+
+        run_files(&[&prelude.0, &main.0], &mut globals()).unwrap();
+    }
+
+    #[test]
+    fn prelude_functions_are_callable_in_the_repl() {
+        let prelude = TempFile::new("repl-prelude.yaipl", "square = (n) { n * n; }\n");
+
+        let mut env = globals();
+        run_files(&[&prelude.0], &mut env).unwrap();
+
+        assert_eq!(repl(&ColorChoice::Never.palette(), env, &mut "exit(square(3))\n".as_bytes()), 9);
+    }
+
+    #[test]
+    fn prelude_may_redefine_a_builtin() {
+        let prelude = TempFile::new("redefining-prelude.yaipl", "typeof = (a, b) { a + b; }\n");
+        let main = TempFile::new("uses-redefined.yaipl", "assert_eq(typeof(1, 2), 3)\n");
+
+        run_files(&[&prelude.0, &main.0], &mut globals()).unwrap();
+    }
+
+    #[test]
+    fn prelude_errors_stop_before_the_program() {
+        let prelude = TempFile::new("broken-prelude.yaipl", "x = * 2\n");
+        let main = TempFile::new("after-broken-prelude.yaipl", "exit(5)\n");
+
+        let error = run_files(&[&prelude.0, &main.0], &mut globals()).unwrap_err();
+        assert!(error.downcast_ref::<SyntaxErrors>().is_some(), "{}", error);
+    }
+
+    #[test]
+    fn syntax_errors_exit_with_the_data_error_code() {
+        let unlexable = TempFile::new("unlexable.yaipl", "x = 1.2.3\n");
+        let unparsable = TempFile::new("unparsable.yaipl", "x = * 2\n");
+
+        for file in [unlexable, unparsable] {
+            let error = check_file(&file.0, LexerOptions::default(), AnalyzerOptions::default(), &ColorChoice::Never.palette()).unwrap_err();
+            assert_eq!(failure_code(&error), EXIT_SYNTAX_ERROR, "{}", error);
+        }
+    }
+
+    #[test]
+    fn runtime_errors_exit_with_the_software_error_code() {
+        let file = TempFile::new("divides-by-zero.yaipl", "x = 0\n1 / x\n");
+        let error = run_file(&file.0).unwrap_err();
+
+        assert_eq!(failure_code(&error), EXIT_RUNTIME_ERROR, "{}", error);
+    }
+}
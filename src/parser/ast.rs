@@ -1,6 +1,6 @@
 use std::fmt::Display;
 
-use crate::lexer::token::{Token, TokenType};
+use crate::lexer::token::{Position, Token, TokenType};
 
 macro_rules! create_struct {
     ($name:ident $(, $field_type:ty)*) => {
@@ -71,6 +71,7 @@ pub enum LogicalOperator {
     GreaterThan,
     LesserThanEqual,
     GreaterThanEqual,
+    In,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -128,28 +129,46 @@ pub fn op_token_to_logical(op: &Token) -> Option<LogicalOperator> {
         TokenType::GreaterThan => Some(LogicalOperator::GreaterThan),
         TokenType::LesserThanEqual => Some(LogicalOperator::LesserThanEqual),
         TokenType::GreaterThanEqual => Some(LogicalOperator::GreaterThanEqual),
+        TokenType::In => Some(LogicalOperator::In),
         _ => None
     }
 }
 
-create_struct!(Assignment, Identifier, Box<Node>);
+create_struct!(Assignment, Identifier, Box<Node>, Position);
 create_struct!(Identifier, String);
-create_struct!(BinaryExpression, Box<Expression>, Operator, Box<Expression>);
+// Left operand, operator, right operand, position of the operator, start of each operand
+create_struct!(BinaryExpression, Box<Expression>, Operator, Box<Expression>, Position, (Position, Position));
 create_struct!(UnaryExpression, Operator, Box<Expression>);
-create_struct!(FunctionCallExpression, Identifier, Vec<Expression>);
-create_struct!(FunctionDeclareExpression, Identifier, Vec<Identifier>, Box<BlockStatement>);
+create_struct!(FunctionCallExpression, Identifier, Vec<Expression>, Position);
+// Calls the result of any other expression, e.g. `list[0](1)`
+create_struct!(CallExpression, Box<Expression>, Vec<Expression>, Position);
+create_struct!(IndexExpression, Box<Expression>, Box<Expression>, Position);
+create_struct!(MethodCallExpression, Box<Expression>, Identifier, Vec<Expression>, Position);
+// Reads a property without calling anything, e.g. `list.length`
+create_struct!(PropertyExpression, Box<Expression>, Identifier, Position);
+// Name, default value, whether it collects the remaining arguments (`...name`), position
+create_struct!(Parameter, Identifier, Option<Expression>, bool, Position);
+create_struct!(FunctionDeclareExpression, Identifier, Vec<Parameter>, Box<BlockStatement>);
+// Pattern (`None` for the `_` wildcard), body
+create_struct!(MatchArm, Option<Literal>, Expression);
+create_struct!(MatchExpression, Box<Expression>, Vec<MatchArm>, Position);
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Expression {
     AssignmentExpr(Assignment),
     LiteralExpr(Literal),
-    IdentifierExpr(Identifier),
+    IdentifierExpr(Identifier, Position),
     BinaryExpr(BinaryExpression),
     UnaryExpr(UnaryExpression),
     GroupExpr(Box<Expression>),
     BlockExpr(BlockStatement),
     FunctionCallExpr(FunctionCallExpression),
+    CallExpr(CallExpression),
+    IndexExpr(IndexExpression),
+    MethodCallExpr(MethodCallExpression),
+    PropertyExpr(PropertyExpression),
     FunctionDeclareExpr(FunctionDeclareExpression),
+    MatchExpr(MatchExpression),
 }
 
 impl Display for Expression {
@@ -166,14 +185,16 @@ pub type ProgramTree = Vec<Node>;
 pub type Program = Node;
 create_struct!(BlockStatement, Vec<Node>);
 create_struct!(EmptyStatement);
-create_struct!(ContinueStatement);
-create_struct!(BreakStatement);
-create_struct!(ExpressionStatement, Expression);
-create_struct!(ReturnStatement, Option<Expression>);
-create_struct!(IfStatement, Expression, Box<BlockStatement>, Option<Box<Node>>);
+create_struct!(ContinueStatement, Position);
+create_struct!(BreakStatement, Position);
+create_struct!(ExpressionStatement, Expression, Position);
+create_struct!(ReturnStatement, Option<Expression>, Position);
+create_struct!(IfStatement, Expression, Box<BlockStatement>, Option<Box<Node>>, Position);
 create_struct!(ElseStatement, Box<BlockStatement>);
-create_struct!(WhileStatement, Expression, Box<BlockStatement>);
-create_struct!(ForStatement, Expression, Expression, Expression, Box<BlockStatement>);
+create_struct!(WhileStatement, Expression, Box<BlockStatement>, Position);
+create_struct!(ForStatement, Expression, Expression, Expression, Box<BlockStatement>, Position);
+create_struct!(ThrowStatement, Expression, Position);
+create_struct!(TryStatement, Box<BlockStatement>, Identifier, Box<BlockStatement>, Position);
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Node {
@@ -188,4 +209,238 @@ pub enum Node {
     ElseStatement(ElseStatement),
     WhileStatement(WhileStatement),
     ForStatement(ForStatement),
+    ThrowStatement(ThrowStatement),
+    TryStatement(TryStatement),
+}
+
+impl Operator {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Operator::Arithmetic(op) => match op {
+                ArithmeticOperator::Plus => "+",
+                ArithmeticOperator::Minus => "-",
+                ArithmeticOperator::Divide => "/",
+                ArithmeticOperator::Multiply => "*",
+                ArithmeticOperator::Modulo => "%",
+                ArithmeticOperator::Power => "^",
+            },
+            Operator::Assignment(op) => match op {
+                AssignmentOperator::PlusAssign => "+=",
+                AssignmentOperator::MinusAssign => "-=",
+                AssignmentOperator::DivideAssign => "/=",
+                AssignmentOperator::MultiplyAssign => "*=",
+                AssignmentOperator::ModuloAssign => "%=",
+                AssignmentOperator::PowerAssign => "^=",
+                AssignmentOperator::Assign => "=",
+            },
+            Operator::Logical(op) => match op {
+                LogicalOperator::Or => "||",
+                LogicalOperator::And => "&&",
+                LogicalOperator::Not => "!",
+                LogicalOperator::Equal => "==",
+                LogicalOperator::NotEqual => "!=",
+                LogicalOperator::LesserThan => "<",
+                LogicalOperator::GreaterThan => ">",
+                LogicalOperator::LesserThanEqual => "<=",
+                LogicalOperator::GreaterThanEqual => ">=",
+                LogicalOperator::In => "in",
+            },
+        }
+    }
+}
+
+/// Appends a line to an indented tree, two spaces per level
+fn push_line(out: &mut String, depth: usize, line: &str) {
+    out.push_str(&"  ".repeat(depth));
+    out.push_str(line);
+    out.push('\n');
+}
+
+impl Node {
+    /// Renders the node as an indented tree, one node per line, which is easier to scan than `{:#?}`
+    pub fn pretty(&self) -> String {
+        let mut out = String::new();
+        self.write_tree(&mut out, 0);
+        out
+    }
+
+    /// The top level statements if this is the program returned by `Parser::parse`
+    pub fn as_program(&self) -> Option<&[Node]> {
+        match self {
+            Node::Program(statements) => Some(statements),
+            _ => None
+        }
+    }
+
+    /// Where the statement starts, `None` for containers such as programs and blocks
+    pub fn position(&self) -> Option<&Position> {
+        match self {
+            Node::ExpressionStatement(ExpressionStatement(_, pos))
+            | Node::ReturnStatement(ReturnStatement(_, pos))
+            | Node::ContinueStatement(ContinueStatement(pos))
+            | Node::BreakStatement(BreakStatement(pos))
+            | Node::IfStatement(IfStatement(.., pos))
+            | Node::WhileStatement(WhileStatement(.., pos))
+            | Node::ForStatement(ForStatement(.., pos))
+            | Node::ThrowStatement(ThrowStatement(_, pos))
+            | Node::TryStatement(TryStatement(.., pos)) => Some(pos),
+            _ => None
+        }
+    }
+
+    fn write_tree(&self, out: &mut String, depth: usize) {
+        match self {
+            Node::Program(nodes) => {
+                push_line(out, depth, "Program");
+                nodes.iter().for_each(|node| node.write_tree(out, depth + 1));
+            },
+            Node::BlockStatement(block) => block.write_tree(out, depth),
+            Node::ExpressionStatement(ExpressionStatement(expression, _)) => {
+                push_line(out, depth, "ExpressionStatement");
+                expression.write_tree(out, depth + 1);
+            },
+            Node::EmptyStatement(_) => push_line(out, depth, "EmptyStatement"),
+            Node::ReturnStatement(ReturnStatement(expression, _)) => {
+                push_line(out, depth, "ReturnStatement");
+                if let Some(expression) = expression {
+                    expression.write_tree(out, depth + 1);
+                }
+            },
+            Node::ContinueStatement(_) => push_line(out, depth, "ContinueStatement"),
+            Node::BreakStatement(_) => push_line(out, depth, "BreakStatement"),
+            Node::IfStatement(IfStatement(condition, body, otherwise, _)) => {
+                push_line(out, depth, "IfStatement");
+                condition.write_tree(out, depth + 1);
+                body.write_tree(out, depth + 1);
+
+                if let Some(otherwise) = otherwise {
+                    otherwise.write_tree(out, depth + 1);
+                }
+            },
+            Node::ElseStatement(ElseStatement(body)) => {
+                push_line(out, depth, "ElseStatement");
+                body.write_tree(out, depth + 1);
+            },
+            Node::WhileStatement(WhileStatement(condition, body, _)) => {
+                push_line(out, depth, "WhileStatement");
+                condition.write_tree(out, depth + 1);
+                body.write_tree(out, depth + 1);
+            },
+            Node::ForStatement(ForStatement(setter, condition, assignment, body, _)) => {
+                push_line(out, depth, "ForStatement");
+                setter.write_tree(out, depth + 1);
+                condition.write_tree(out, depth + 1);
+                assignment.write_tree(out, depth + 1);
+                body.write_tree(out, depth + 1);
+            },
+            Node::ThrowStatement(ThrowStatement(expression, _)) => {
+                push_line(out, depth, "ThrowStatement");
+                expression.write_tree(out, depth + 1);
+            },
+            Node::TryStatement(TryStatement(body, identifier, handler, _)) => {
+                push_line(out, depth, &format!("TryStatement({})", identifier.0));
+                body.write_tree(out, depth + 1);
+                handler.write_tree(out, depth + 1);
+            },
+        }
+    }
+}
+
+impl BlockStatement {
+    fn write_tree(&self, out: &mut String, depth: usize) {
+        push_line(out, depth, "BlockStatement");
+        self.0.iter().for_each(|node| node.write_tree(out, depth + 1));
+    }
+}
+
+impl Expression {
+    /// Renders the expression as an indented tree, see `Node::pretty`
+    pub fn pretty(&self) -> String {
+        let mut out = String::new();
+        self.write_tree(&mut out, 0);
+        out
+    }
+
+    fn write_tree(&self, out: &mut String, depth: usize) {
+        match self {
+            Expression::AssignmentExpr(Assignment(identifier, value, _)) => {
+                push_line(out, depth, &format!("Assignment({})", identifier.0));
+                value.write_tree(out, depth + 1);
+            },
+            Expression::LiteralExpr(Literal::List(ListLiteral(elements))) => {
+                push_line(out, depth, "List");
+                elements.iter().for_each(|element| element.write_tree(out, depth + 1));
+            },
+            Expression::LiteralExpr(Literal::String(value)) => push_line(out, depth, &format!("String({:?})", value.0)),
+            Expression::LiteralExpr(Literal::Integer(value)) => push_line(out, depth, &format!("Integer({})", value.0)),
+            Expression::LiteralExpr(Literal::Float(value)) => push_line(out, depth, &format!("Float({})", value.0)),
+            Expression::LiteralExpr(Literal::Boolean(value)) => push_line(out, depth, &format!("Boolean({})", value.0)),
+            Expression::LiteralExpr(Literal::Null) => push_line(out, depth, "Null"),
+            Expression::IdentifierExpr(identifier, _) => push_line(out, depth, &format!("Identifier({})", identifier.0)),
+            Expression::BinaryExpr(BinaryExpression(left, operator, right, _, _)) => {
+                push_line(out, depth, &format!("BinaryExpression({})", operator.symbol()));
+                left.write_tree(out, depth + 1);
+                right.write_tree(out, depth + 1);
+            },
+            Expression::UnaryExpr(UnaryExpression(operator, expression)) => {
+                push_line(out, depth, &format!("UnaryExpression({})", operator.symbol()));
+                expression.write_tree(out, depth + 1);
+            },
+            Expression::GroupExpr(expression) => {
+                push_line(out, depth, "Group");
+                expression.write_tree(out, depth + 1);
+            },
+            Expression::BlockExpr(block) => block.write_tree(out, depth),
+            Expression::FunctionCallExpr(FunctionCallExpression(identifier, arguments, _)) => {
+                push_line(out, depth, &format!("FunctionCall({})", identifier.0));
+                arguments.iter().for_each(|argument| argument.write_tree(out, depth + 1));
+            },
+            Expression::CallExpr(CallExpression(callee, arguments, _)) => {
+                push_line(out, depth, "Call");
+                callee.write_tree(out, depth + 1);
+                arguments.iter().for_each(|argument| argument.write_tree(out, depth + 1));
+            },
+            Expression::IndexExpr(IndexExpression(target, index, _)) => {
+                push_line(out, depth, "Index");
+                target.write_tree(out, depth + 1);
+                index.write_tree(out, depth + 1);
+            },
+            Expression::MethodCallExpr(MethodCallExpression(receiver, method, arguments, _)) => {
+                push_line(out, depth, &format!("MethodCall({})", method.0));
+                receiver.write_tree(out, depth + 1);
+                arguments.iter().for_each(|argument| argument.write_tree(out, depth + 1));
+            },
+            Expression::PropertyExpr(PropertyExpression(receiver, property, _)) => {
+                push_line(out, depth, &format!("Property({})", property.0));
+                receiver.write_tree(out, depth + 1);
+            },
+            Expression::FunctionDeclareExpr(FunctionDeclareExpression(identifier, parameters, body)) => {
+                push_line(out, depth, &format!("FunctionDeclare({})", identifier.0));
+
+                for Parameter(identifier, default, rest, _) in parameters {
+                    let prefix = if *rest { "..." } else { "" };
+                    push_line(out, depth + 1, &format!("Parameter({}{})", prefix, identifier.0));
+
+                    if let Some(default) = default {
+                        default.write_tree(out, depth + 2);
+                    }
+                }
+
+                body.write_tree(out, depth + 1);
+            },
+            Expression::MatchExpr(MatchExpression(scrutinee, arms, _)) => {
+                push_line(out, depth, "Match");
+                scrutinee.write_tree(out, depth + 1);
+
+                for MatchArm(pattern, body) in arms {
+                    match pattern {
+                        Some(pattern) => push_line(out, depth + 1, &format!("Arm({})", pattern)),
+                        None => push_line(out, depth + 1, "Arm(_)"),
+                    }
+
+                    body.write_tree(out, depth + 2);
+                }
+            },
+        }
+    }
 }
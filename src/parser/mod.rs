@@ -1,51 +1,161 @@
 use std::{error::Error, vec};
 
-use crate::{error, errors::{DynamicError, ParserError}, evaluator::object::FUNCTION_PREFIX, lexer::token::{Token, TokenLiteral, TokenType, Tokens}, parser::ast::Literal, utils::unwrap_result};
+use crate::{error, errors::{DynamicError, LexerError, ParserError}, evaluator::object::{DISCARD, FUNCTION_PREFIX}, lexer::token::{Position, Token, TokenLiteral, TokenType, Tokens}, parser::ast::Literal, utils::unwrap_result};
 
-use self::ast::{assignment_to_arithmetic, op_token_to_arithmetic, op_token_to_assignment, op_token_to_logical, BlockStatement, EmptyStatement, Expression, ExpressionStatement, Identifier, Node, Program};
+use self::ast::{assignment_to_arithmetic, op_token_to_arithmetic, op_token_to_assignment, op_token_to_logical, BlockStatement, EmptyStatement, Expression, ExpressionStatement, Identifier, MatchArm, Node, Parameter, Program};
 
 pub mod ast;
 
 type ParserResult<T> = Result<T, DynamicError>;
 
+#[derive(Debug, Clone)]
+pub struct ParserOptions {
+    /// How deeply expressions may nest before parsing fails, so deep input can't overflow the stack
+    pub max_depth: usize,
+}
+
+impl Default for ParserOptions {
+    fn default() -> Self {
+        Self {
+            // Each level takes around 16KB of stack in debug builds, so this fits in the 8MB the main thread gets
+            max_depth: 256,
+        }
+    }
+}
+
 pub struct Parser<'a> {
+    pub options: ParserOptions,
     pub tokens: &'a Tokens,
     warnings: Vec<Box<dyn Error>>,
     current: usize,
+    depth: usize,
 }
 
 impl<'a> Parser<'a> {
     pub fn from(tokens: &'a Tokens) -> Self {
         Self {
+            options: ParserOptions::default(),
             tokens,
             current: 0,
             warnings: Vec::new(),
+            depth: 0,
         }
     }
 
     pub fn parse(&mut self) -> Result<Program, DynamicError> {
+        self.check_delimiters()?;
         Ok(Node::Program(self.parse_statements()?))
     }
 
+    /// Parses like `parse`, but after an error skips to the next statement and carries on,
+    /// so every error in the program is returned rather than only the first
+    pub fn parse_recovering(&mut self) -> Result<Program, Vec<DynamicError>> {
+        self.check_delimiters().map_err(|err| vec![err])?;
+
+        let mut statements: Vec<Node> = Vec::new();
+        let mut errors: Vec<DynamicError> = Vec::new();
+
+        self.skip_line_ends();
+        while !self.is_at_end() {
+            let start = self.current;
+            match self.declaration() {
+                Ok(statement) => statements.push(statement),
+                Err(err) => {
+                    errors.push(err);
+                    self.synchronize(start);
+                }
+            }
+            self.skip_line_ends();
+        }
+
+        if errors.is_empty() {
+            Ok(Node::Program(statements))
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Skips past the end of the statement which started at `start`, including any blocks it
+    /// had open when it failed
+    fn synchronize(&mut self, start: usize) {
+        let mut depth = self.tokens[start..self.current].iter().fold(0, |depth, token| match token.token_type {
+            TokenType::LeftBrace => depth + 1,
+            TokenType::RightBrace => depth - 1,
+            _ => depth
+        });
+
+        while let Some(token) = self.peek().filter(|_| !self.is_at_end()) {
+            match token.token_type {
+                TokenType::LeftBrace => depth += 1,
+                TokenType::RightBrace => depth -= 1,
+                TokenType::EndOfLine if depth <= 0 => {
+                    self.advance();
+                    return;
+                },
+                _ => {}
+            }
+
+            self.advance();
+        }
+    }
+
+    /// Reports the opening `(`, `[` or `{` of any unclosed pair up front, rather than
+    /// failing wherever the parser happens to run into the mismatch
+    fn check_delimiters(&self) -> ParserResult<()> {
+        let mut open: Vec<&Token> = Vec::new();
+
+        for token in self.tokens {
+            let opener = match token.token_type {
+                TokenType::LeftParen | TokenType::LeftBracket | TokenType::LeftBrace => {
+                    open.push(token);
+                    continue;
+                },
+                TokenType::RightParen => TokenType::LeftParen,
+                TokenType::RightBracket => TokenType::LeftBracket,
+                TokenType::RightBrace => TokenType::LeftBrace,
+                _ => continue
+            };
+
+            match open.pop() {
+                Some(last) if last.token_type != opener => error!(ParserError::UnclosedDelimiter {
+                    delimiter: last.token_type.to_owned(),
+                    pos: last.start.to_owned(),
+                }),
+                // Stray closing delimiters are left for the parser to report
+                _ => {}
+            }
+        }
+
+        if let Some(last) = open.pop() {
+            error!(ParserError::UnclosedDelimiter {
+                delimiter: last.token_type.to_owned(),
+                pos: last.start.to_owned(),
+            })
+        }
+
+        Ok(())
+    }
+
     fn parse_statements(&mut self) -> ParserResult<Vec<Node>> {
         let mut statements: Vec<Node> = Vec::new();
 
+        // Blank lines and stray terminators between statements don't produce empty statements
+        self.skip_line_ends();
         while !self.is_at_end() {
             let statement = self.declaration()?;
             statements.push(statement);
+            self.skip_line_ends();
         }
 
         Ok(statements)
     }
 
     fn declaration(&mut self) -> ParserResult<Node> {
-        if self.check(TokenType::Symbol) {
-            if unwrap_result(self.lookahead())?.token_type == TokenType::Assign {
-                return Ok(self.var_declaration()?)
-            }
+        if self.check(TokenType::Symbol) && unwrap_result(self.lookahead())?.token_type == TokenType::Assign {
+            return self.var_declaration();
         }
 
-        Ok(self.statement()?)
+        self.statement()
     }
 
     fn var_declaration(&mut self) -> ParserResult<Node> {
@@ -64,14 +174,13 @@ impl<'a> Parser<'a> {
         
         // Attempt to collect parameters for function declaration
         if self.matches(TokenType::LeftParen) {
-            match self.collect_parameters() {
-                Ok(parameters) => {
-                    if self.check(TokenType::LeftBrace) {
-                        return self.func_declaration(Identifier(name), parameters);
-                    }
-                },
-                Err(_) => {} // Collecting parameters failed
-            };
+            // Collecting parameters may fail, in which case this is not a function declaration
+            if let Ok(parameters) = self.collect_parameters() {
+                if self.check(TokenType::LeftBrace) {
+                    Self::validate_parameters(&parameters)?;
+                    return self.func_declaration(Identifier(name), parameters, symbol.start);
+                }
+            }
         }
         self.current = old_current; // Reset current to before the failed attempt
 
@@ -81,18 +190,22 @@ impl<'a> Parser<'a> {
             Expression::AssignmentExpr(ast::Assignment(
                 ast::Identifier(name),
                 Box::from(initializer),
-            ))
+                symbol.start.to_owned(),
+            )),
+            symbol.start,
         )))
     }
     
-    fn collect_parameters(&mut self) -> ParserResult<Vec<Identifier>> {
-        let mut arguments: Vec<Identifier> = Vec::new();
+    fn collect_parameters(&mut self) -> ParserResult<Vec<Parameter>> {
+        let mut arguments: Vec<Parameter> = Vec::new();
         
         loop {
+            self.skip_line_ends();
             if self.matches(TokenType::RightParen) {
                 break;
             }
             
+            let rest = self.matches(TokenType::Spread);
             let symbol = self.consume(TokenType::Symbol)?;
             let name = match unwrap_result(symbol.value)? {
                 TokenLiteral::String(name) => name,
@@ -102,20 +215,57 @@ impl<'a> Parser<'a> {
                     pos: symbol.start,
                 }),
             };
+
+            let default = if !rest && self.matches(TokenType::Assign) {
+                Some(self.expression()?)
+            } else {
+                None
+            };
+
+            arguments.push(Parameter(Identifier(name), default, rest, symbol.start));
+            self.skip_line_ends();
             
-            arguments.push(Identifier(name));
-            
-            if !self.matches(TokenType::Comma) {
-                if self.matches(TokenType::RightParen) {
-                    break;
-                }
+            if !self.matches(TokenType::Comma) && self.matches(TokenType::RightParen) {
+                break;
             }
         }
 
         Ok(arguments)
     }
 
-    fn func_declaration(&mut self, mut identifier: Identifier, parameters: Vec<Identifier>) -> ParserResult<Node> {
+    fn validate_parameters(parameters: &[Parameter]) -> ParserResult<()> {
+        let mut has_default = false;
+
+        for (index, Parameter(identifier, default, rest, pos)) in parameters.iter().enumerate() {
+            // Several parameters may be discarded, but a name can only be bound once
+            if identifier.0 != DISCARD && parameters[..index].iter().any(|parameter| parameter.0 == *identifier) {
+                error!(ParserError::DuplicateParameter {
+                    name: identifier.0.to_owned(),
+                    pos: pos.to_owned(),
+                })
+            }
+
+            if *rest {
+                if index != parameters.len() - 1 {
+                    error!(ParserError::RestParameterNotLast {
+                        name: identifier.0.to_owned(),
+                        pos: pos.to_owned(),
+                    })
+                }
+            } else if default.is_some() {
+                has_default = true;
+            } else if has_default {
+                error!(ParserError::RequiredAfterDefault {
+                    name: identifier.0.to_owned(),
+                    pos: pos.to_owned(),
+                })
+            }
+        }
+
+        Ok(())
+    }
+
+    fn func_declaration(&mut self, mut identifier: Identifier, parameters: Vec<Parameter>, pos: Position) -> ParserResult<Node> {
         let body = self.block()?;
 
         identifier.0 = format!("{}{}", FUNCTION_PREFIX, identifier.0);
@@ -127,16 +277,23 @@ impl<'a> Parser<'a> {
                     parameters,
                     Box::from(body)
                 )
-            )
+            ),
+            pos,
         )))
     }
 
     fn block(&mut self) -> ParserResult<BlockStatement> {
+        self.nested(Self::block_body)
+    }
+
+    fn block_body(&mut self) -> ParserResult<BlockStatement> {
         self.consume(TokenType::LeftBrace)?;
         let mut statements: Vec<Node> = Vec::new();
 
+        self.skip_line_ends();
         while !self.is_at_end() && !self.check(TokenType::RightBrace) {
             statements.push(self.declaration()?);
+            self.skip_line_ends();
         }
 
         self.consume(TokenType::RightBrace)?;
@@ -163,17 +320,27 @@ impl<'a> Parser<'a> {
         }
 
         if self.matches(TokenType::Break) {
-            return Ok(Node::BreakStatement(ast::BreakStatement()));
+            let pos = unwrap_result(self.previous())?.start.to_owned();
+            return Ok(Node::BreakStatement(ast::BreakStatement(pos)));
         }
 
         if self.matches(TokenType::Continue) {
-            return Ok(Node::ContinueStatement(ast::ContinueStatement()));
+            let pos = unwrap_result(self.previous())?.start.to_owned();
+            return Ok(Node::ContinueStatement(ast::ContinueStatement(pos)));
         }
 
         if self.matches(TokenType::Return) {
             return self.return_statement();
         }
 
+        if self.matches(TokenType::Throw) {
+            return self.throw_statement();
+        }
+
+        if self.matches(TokenType::Try) {
+            return self.try_statement();
+        }
+
         Ok(Node::ExpressionStatement(self.expression_statement()?))
     }
 
@@ -183,6 +350,7 @@ impl<'a> Parser<'a> {
     }
 
     fn if_statement(&mut self) -> ParserResult<Node> {
+        let pos = unwrap_result(self.previous())?.start.to_owned();
         let condition = self.expression()?;
         let body = self.block()?;
 
@@ -194,7 +362,13 @@ impl<'a> Parser<'a> {
                 },
                 TokenType::Else => {
                     self.consume(TokenType::Else)?;
-                    Some(Box::from(self.else_statement()?))
+
+                    // `else if` chains the same way as `elif`
+                    if self.matches(TokenType::If) {
+                        Some(Box::from(self.if_statement()?))
+                    } else {
+                        Some(Box::from(self.else_statement()?))
+                    }
                 },
                 _ => None
             }
@@ -207,11 +381,13 @@ impl<'a> Parser<'a> {
                 condition,
                 Box::from(body),
                 maybe_else,
+                pos,
             )
         ))
     }
 
     fn while_statement(&mut self) -> ParserResult<Node> {
+        let pos = unwrap_result(self.previous())?.start.to_owned();
         let condition = self.expression()?;
         let body = self.block()?;
 
@@ -219,15 +395,17 @@ impl<'a> Parser<'a> {
             ast::WhileStatement(
                 condition,
                 Box::from(body),
+                pos,
             )
         ))
     }
 
     fn for_statement(&mut self) -> ParserResult<Node> {
+        let pos = unwrap_result(self.previous())?.start.to_owned();
         let _ = self.consume(TokenType::LeftParen);
         let variable = self.var_declaration()?;
 
-        let condition = self.or()?;
+        let condition = self.binary(0)?;
         self.consume(TokenType::EndOfLine)?;
         
         let assignment = self.assignment()?;
@@ -236,7 +414,7 @@ impl<'a> Parser<'a> {
         let body = self.block()?;
 
         let variable = match variable {
-            Node::ExpressionStatement(ExpressionStatement(assignment)) => assignment,
+            Node::ExpressionStatement(ExpressionStatement(assignment, _)) => assignment,
             _ => error!(ParserError::InvalidStatement)
         };
 
@@ -246,11 +424,13 @@ impl<'a> Parser<'a> {
                 condition,
                 assignment,
                 Box::from(body),
+                pos,
             )
         ))
     }
 
     fn return_statement(&mut self) -> ParserResult<Node> {
+        let pos = unwrap_result(self.previous())?.start.to_owned();
         let return_value = if !self.matches(TokenType::EndOfLine) {
             Some(self.expression()?)
         } else {
@@ -262,26 +442,77 @@ impl<'a> Parser<'a> {
         }
 
         Ok(Node::ReturnStatement(ast::ReturnStatement(
-            return_value
+            return_value,
+            pos
+        )))
+    }
+
+    fn throw_statement(&mut self) -> ParserResult<Node> {
+        let pos = unwrap_result(self.previous())?.start.to_owned();
+        let value = self.expression()?;
+        self.consume(TokenType::EndOfLine)?;
+
+        Ok(Node::ThrowStatement(ast::ThrowStatement(value, pos)))
+    }
+
+    fn try_statement(&mut self) -> ParserResult<Node> {
+        let pos = unwrap_result(self.previous())?.start.to_owned();
+        let body = self.block()?;
+
+        self.consume(TokenType::Catch)?;
+        let symbol = self.consume(TokenType::Symbol)?;
+        let name = match unwrap_result(symbol.value)? {
+            TokenLiteral::String(name) => name,
+            _ => error!(ParserError::InvalidToken {
+                expected: vec![TokenType::Symbol],
+                found: symbol.token_type,
+                pos: symbol.start,
+            }),
+        };
+
+        let handler = self.block()?;
+
+        Ok(Node::TryStatement(ast::TryStatement(
+            Box::from(body),
+            Identifier(name),
+            Box::from(handler),
+            pos,
         )))
     }
 
     fn expression_statement(&mut self) -> ParserResult<ExpressionStatement> {
+        let pos = unwrap_result(self.peek())?.start.to_owned();
         let expression = self.expression()?;
         if let Some(token) = self.previous() {
             if token.token_type != TokenType::RightBrace {
                 self.consume(TokenType::EndOfLine)?;
             }
         }
-        Ok(ExpressionStatement(expression))
+        Ok(ExpressionStatement(expression, pos))
     }
 
     fn expression(&mut self) -> ParserResult<Expression> {
-        self.assignment()
+        self.nested(Self::assignment)
+    }
+
+    /// Runs a parse step one nesting level deeper, failing once the depth limit is exceeded
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> ParserResult<T>) -> ParserResult<T> {
+        if self.depth >= self.options.max_depth {
+            error!(ParserError::NestingTooDeep {
+                limit: self.options.max_depth,
+                pos: unwrap_result(self.peek())?.start.to_owned(),
+            })
+        }
+
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
     }
 
     fn assignment(&mut self) -> ParserResult<Expression> {
-        let expression = self.or()?;
+        let start = unwrap_result(self.peek())?.start.to_owned();
+        let expression = self.pipe()?;
 
         if self.match_one_of(vec![
             TokenType::PlusAssign,
@@ -291,9 +522,10 @@ impl<'a> Parser<'a> {
             TokenType::ModuloAssign,
         ]) {
             let operator = unwrap_result(self.previous())?.to_owned();
+            let value_start = unwrap_result(self.peek())?.start.to_owned();
             let value = self.assignment()?;
 
-            if let Expression::IdentifierExpr(identifier) = &expression {
+            if let Expression::IdentifierExpr(identifier, _) = &expression {
                 let ast_op = unwrap_result(op_token_to_assignment(&operator))?;
                 let arithmetic_op = unwrap_result(assignment_to_arithmetic(&ast_op))?;
 
@@ -305,9 +537,13 @@ impl<'a> Parser<'a> {
                                 Box::new(expression),
                                 ast::Operator::Arithmetic(arithmetic_op),
                                 Box::new(value),
-                            ))
+                                operator.start,
+                                (start.to_owned(), value_start),
+                            )),
+                            start.to_owned(),
                         )
                     )),
+                    start,
                 )))
             }
         }
@@ -315,144 +551,85 @@ impl<'a> Parser<'a> {
         Ok(expression)
     }
 
-    fn or(&mut self) -> ParserResult<Expression> {
-        let mut expression = self.and()?;
-
-        while self.matches(TokenType::Or) {
-            let right = self.and()?;
-            expression = Expression::BinaryExpr(ast::BinaryExpression(
-                Box::new(expression), 
-                ast::Operator::Logical(ast::LogicalOperator::Or), 
-                Box::new(right)
-            ));
-        }
-
-        Ok(expression)
-    }
-
-    fn and(&mut self) -> ParserResult<Expression> {
-        let mut expression = self.equality()?;
-
-        while self.matches(TokenType::And) {
-            let right = self.equality()?;
-            expression = Expression::BinaryExpr(ast::BinaryExpression(
-                Box::new(expression), 
-                ast::Operator::Logical(ast::LogicalOperator::And), 
-                Box::new(right)
-            ));
-        }
-
-        Ok(expression)
-    }
-
-    fn equality(&mut self) -> ParserResult<Expression> {
-        let mut expression = self.comparison()?;
-
-        while self.match_one_of(vec![TokenType::Equal, TokenType::NotEqual]) {
-            let operator = unwrap_result(self.previous())?.to_owned();
-            let right = self.comparison()?;
-        
-            match op_token_to_logical(&operator) {
-                None => error!(ParserError::InvalidToken {
-                    expected: vec![TokenType::Equal, TokenType::NotEqual],
-                    found: operator.token_type,
-                    pos: operator.start,
-                }),
-                Some(op) => {
-                    expression = Expression::BinaryExpr(ast::BinaryExpression(
-                        Box::new(expression),
-                        ast::Operator::Logical(op),
-                        Box::new(right),
-                    ))
-                }
-            }
-        }
-
-        Ok(expression)
-    }
-
-    fn comparison(&mut self) -> ParserResult<Expression> {
-        let mut expression = self.addition()?;
-
-        while self.match_one_of(vec![
-            TokenType::LesserThan,
-            TokenType::GreaterThan,
-            TokenType::LesserThanEqual,
-            TokenType::GreaterThanEqual,
-        ]) {
-            let operator = unwrap_result(self.previous())?.to_owned();
-            let right = self.addition()?;
+    fn pipe(&mut self) -> ParserResult<Expression> {
+        let mut expression = self.binary(0)?;
 
-            let comparison_operator = unwrap_result(op_token_to_logical(&operator))?;
+        // `x |> f` desugars to `f(x)` and `x |> f(y)` to `f(x, y)`
+        while self.matches(TokenType::Pipe) {
+            let token = unwrap_result(self.peek())?.to_owned();
 
-            expression = Expression::BinaryExpr(ast::BinaryExpression(
-                Box::new(expression), 
-                ast::Operator::Logical(comparison_operator), 
-                Box::new(right)
-            ));
+            expression = match self.binary(0)? {
+                Expression::IdentifierExpr(Identifier(name), _) => Expression::FunctionCallExpr(ast::FunctionCallExpression(
+                    Identifier(format!("{}{}", FUNCTION_PREFIX, name)),
+                    vec![expression],
+                    token.start,
+                )),
+                Expression::FunctionCallExpr(ast::FunctionCallExpression(name, mut arguments, pos)) => {
+                    arguments.insert(0, expression);
+                    Expression::FunctionCallExpr(ast::FunctionCallExpression(name, arguments, pos))
+                },
+                _ => error!(ParserError::InvalidToken {
+                    expected: vec![TokenType::Symbol],
+                    found: token.token_type,
+                    pos: token.start,
+                })
+            };
         }
 
         Ok(expression)
     }
 
-    fn addition(&mut self) -> ParserResult<Expression> {
-        let mut expression = self.multiplication()?;
-
-        while self.match_one_of(vec![TokenType::Minus, TokenType::Plus]) {
-            let operator = unwrap_result(self.previous())?.to_owned();
-            let right = self.multiplication()?;
-
-            let arithmetic_operator = unwrap_result(op_token_to_arithmetic(&operator))?;
-
-            expression = Expression::BinaryExpr(ast::BinaryExpression(
-                Box::new(expression),
-                ast::Operator::Arithmetic(arithmetic_operator),
-                Box::new(right),
-            ));
-        }
+    /// Precedence climbing over the binary operators, `min_power` being the weakest operator this call may consume
+    fn binary(&mut self, min_power: u8) -> ParserResult<Expression> {
+        let start = unwrap_result(self.peek())?.start.to_owned();
+        let mut expression = self.unary()?;
 
-        Ok(expression)
-    }
+        loop {
+            let token = unwrap_result(self.peek())?.to_owned();
+            let Some((left_power, right_power)) = binding_power(&token.token_type) else {
+                break;
+            };
 
-    fn multiplication(&mut self) -> ParserResult<Expression> {
-        let mut expression = self.exponent()?;
+            if left_power < min_power {
+                break;
+            }
 
-        while self.match_one_of(vec![TokenType::Multiply, TokenType::Divide, TokenType::Modulo]) {
-            let operator = unwrap_result(self.previous())?.to_owned();
-            let right = self.exponent()?;
+            self.advance();
+            let right_start = unwrap_result(self.peek())?.start.to_owned();
+            let right = self.nested(|parser| parser.binary(right_power))?;
 
-            let arithmetic_operator = unwrap_result(op_token_to_arithmetic(&operator))?;
+            let operator = match op_token_to_arithmetic(&token) {
+                Some(operator) => ast::Operator::Arithmetic(operator),
+                None => ast::Operator::Logical(unwrap_result(op_token_to_logical(&token))?),
+            };
 
             expression = Expression::BinaryExpr(ast::BinaryExpression(
                 Box::new(expression),
-                ast::Operator::Arithmetic(arithmetic_operator),
+                operator,
                 Box::new(right),
+                token.start,
+                (start.to_owned(), right_start),
             ));
         }
 
         Ok(expression)
     }
 
-    fn exponent(&mut self) -> ParserResult<Expression> {
-        let mut expression = self.unary()?;
-
-        if self.matches(TokenType::Power) {
-            let right = self.unary()?;
-
-            expression = Expression::BinaryExpr(ast::BinaryExpression(
-                Box::new(expression),
-                ast::Operator::Arithmetic(ast::ArithmeticOperator::Power),
-                Box::new(right),
-            ));
+    fn unary(&mut self) -> ParserResult<Expression> {
+        // `-5` is parsed as one negative literal, so it isn't negated at runtime and `-2147483648`
+        // fits. Not when a postfix follows, as `-5.abs()` negates the result of the method
+        let postfix = self.tokens.get(self.current + 2)
+            .is_some_and(|token| matches!(token.token_type, TokenType::Dot | TokenType::LeftParen | TokenType::LeftBracket));
+
+        if !postfix {
+            if let Some(literal) = self.negative_literal()? {
+                return Ok(Expression::LiteralExpr(literal));
+            }
         }
 
-        Ok(expression)
-    }
-
-    fn unary(&mut self) -> ParserResult<Expression> {
         if self.match_one_of(vec![TokenType::Minus, TokenType::Not]) {
             let operator = unwrap_result(self.previous())?.to_owned();
-            let right = self.unary()?;
+            let right = self.nested(Self::unary)?;
 
             let unary_operator = match operator.token_type {
                 TokenType::Minus => ast::Operator::Arithmetic(ast::ArithmeticOperator::Minus),
@@ -490,11 +667,48 @@ impl<'a> Parser<'a> {
 
     fn call(&mut self) -> ParserResult<Expression> {
         let identifier = unwrap_result(self.peek())?.to_owned();
-        if self.matches_all_in_order(vec![TokenType::Symbol, TokenType::LeftParen]) {
-            return Ok(self.finish_call(identifier.to_owned())?);
+        let mut expression = if self.matches_all_in_order(vec![TokenType::Symbol, TokenType::LeftParen]) {
+            self.finish_call(identifier.to_owned())?
+        } else {
+            self.primary()?
+        };
+
+        // Calls, indexes and method calls chain in any order, e.g. `f()[0]` or `list[0].len()`
+        loop {
+            if self.matches(TokenType::LeftParen) {
+                let pos = unwrap_result(self.previous())?.start.to_owned();
+                let arguments = self.arguments()?;
+                expression = Expression::CallExpr(ast::CallExpression(Box::new(expression), arguments, pos));
+            } else if self.matches(TokenType::LeftBracket) {
+                let pos = unwrap_result(self.previous())?.start.to_owned();
+                let index = self.expression()?;
+                self.consume(TokenType::RightBracket)?;
+                expression = Expression::IndexExpr(ast::IndexExpression(Box::new(expression), Box::new(index), pos));
+            } else if self.matches(TokenType::Dot) {
+                let symbol = self.consume(TokenType::Symbol)?;
+                let name = match unwrap_result(symbol.value)? {
+                    TokenLiteral::String(name) => name,
+                    _ => error!(ParserError::InvalidToken {
+                        expected: vec![TokenType::Symbol],
+                        found: symbol.token_type,
+                        pos: symbol.start,
+                    }),
+                };
+
+                // Without parentheses it reads a property instead of calling a method
+                if !self.matches(TokenType::LeftParen) {
+                    expression = Expression::PropertyExpr(ast::PropertyExpression(Box::new(expression), Identifier(name), symbol.start));
+                    continue;
+                }
+
+                let arguments = self.arguments()?;
+                expression = Expression::MethodCallExpr(ast::MethodCallExpression(Box::new(expression), Identifier(name), arguments, symbol.start));
+            } else {
+                break;
+            }
         }
-        
-        Ok(self.primary()?)
+
+        Ok(expression)
     }
 
     fn finish_call(&mut self, identifier: Token) -> ParserResult<Expression> {
@@ -507,14 +721,26 @@ impl<'a> Parser<'a> {
             })
         };
 
+        Ok(Expression::FunctionCallExpr(ast::FunctionCallExpression(
+            ast::Identifier(name),
+            self.arguments()?,
+            identifier.start
+        )))
+    }
+
+    /// Parses call arguments up to and including the closing paren
+    fn arguments(&mut self) -> ParserResult<Vec<Expression>> {
         let mut arguments: Vec<Expression> = Vec::new();
 
+        // Checking for the closing paren first also accepts a trailing comma, e.g. `f(1, 2,)`
         loop {
+            self.skip_line_ends();
             if self.matches(TokenType::RightParen) {
                 break;
             }
 
             arguments.push(self.expression()?);
+            self.skip_line_ends();
 
             if !self.matches(TokenType::Comma) {
                 self.consume(TokenType::RightParen)?;
@@ -522,10 +748,7 @@ impl<'a> Parser<'a> {
             }
         }
 
-        Ok(Expression::FunctionCallExpr(ast::FunctionCallExpression(
-            ast::Identifier(name),
-            arguments
-        )))
+        Ok(arguments)
     }
 
     fn primary(&mut self) -> ParserResult<Expression> {
@@ -535,7 +758,16 @@ impl<'a> Parser<'a> {
         let result = match token.token_type {
             TokenType::Null => Expression::LiteralExpr(Literal::Null),
             TokenType::Integer => {
-                let value = unwrap_result(value)?.get_value().parse::<i32>()?;
+                let literal = unwrap_result(value)?.get_value();
+
+                // The lexer allows one past the largest integer after a minus, which only fits as a negative literal
+                let Ok(value) = literal.parse::<i32>() else {
+                    error!(LexerError::NumberOutOfRange {
+                        literal,
+                        expected: TokenType::Integer,
+                        pos: token.start,
+                    })
+                };
                 Expression::LiteralExpr(Literal::Integer(ast::IntegerLiteral(value)))
             },
             TokenType::Float => {
@@ -552,27 +784,37 @@ impl<'a> Parser<'a> {
             },
             TokenType::Symbol => {
                 let value = unwrap_result(value)?.get_value();
-                Expression::IdentifierExpr(ast::Identifier(value))
+                if value == DISCARD {
+                    error!(ParserError::DiscardRead { pos: token.start })
+                }
+
+                Expression::IdentifierExpr(ast::Identifier(value), token.start)
             }
             TokenType::LeftBracket => {
                 self.advance();
                 return self.parse_bracket();
             },
+            TokenType::Match => {
+                self.advance();
+                return self.match_expression(token.start);
+            },
             TokenType::LeftBrace => {
                 let block = self.block()?;
                 return Ok(Expression::BlockExpr(block));
             },
             TokenType::LeftParen => {
                 self.advance();
+                self.skip_line_ends();
                 let expression = self.expression()?;
+                self.skip_line_ends();
                 self.consume(TokenType::RightParen)?;
                 return Ok(Expression::GroupExpr(Box::from(expression)));
             },
-            _ => error!(ParserError::InvalidToken {
-                expected: vec![TokenType::Integer, TokenType::Float, TokenType::Boolean, TokenType::String, TokenType::Symbol, TokenType::LeftParen],
-                found: token.token_type,
-                pos: token.start,
-            })
+            _ => return Err(self.invalid_token(
+                vec![TokenType::Integer, TokenType::Float, TokenType::Boolean, TokenType::String, TokenType::Symbol, TokenType::LeftParen],
+                token.token_type,
+                token.start,
+            ))
         };
         
         self.advance();
@@ -583,11 +825,13 @@ impl<'a> Parser<'a> {
         let mut elements: Vec<Expression> = Vec::new();
 
         loop {
+            self.skip_line_ends();
             if self.matches(TokenType::RightBracket) {
                 break;
             }
 
             elements.push(self.expression()?);
+            self.skip_line_ends();
 
             if !self.matches(TokenType::Comma) {
                 self.consume(TokenType::RightBracket)?;
@@ -598,17 +842,104 @@ impl<'a> Parser<'a> {
         Ok(Expression::LiteralExpr(Literal::List(ast::ListLiteral(elements))))
     }
 
+    fn match_expression(&mut self, pos: Position) -> ParserResult<Expression> {
+        let scrutinee = self.expression()?;
+        self.consume(TokenType::LeftBrace)?;
+
+        let mut arms: Vec<MatchArm> = Vec::new();
+
+        loop {
+            self.skip_line_ends();
+            if self.matches(TokenType::RightBrace) {
+                break;
+            }
+
+            let pattern = self.match_pattern()?;
+            self.consume(TokenType::FatArrow)?;
+            arms.push(MatchArm(pattern, self.expression()?));
+            self.skip_line_ends();
+
+            if !self.matches(TokenType::Comma) {
+                self.consume(TokenType::RightBrace)?;
+                break;
+            }
+        }
+
+        Ok(Expression::MatchExpr(ast::MatchExpression(Box::new(scrutinee), arms, pos)))
+    }
+
+    /// Parses a literal pattern, or `None` for the `_` wildcard
+    fn match_pattern(&mut self) -> ParserResult<Option<Literal>> {
+        let token = unwrap_result(self.peek())?.to_owned();
+
+        if token.token_type == TokenType::Symbol && token.value.as_ref().is_some_and(|value| value.get_value() == DISCARD) {
+            self.advance();
+            return Ok(None);
+        }
+
+        if let Some(literal) = self.negative_literal()? {
+            return Ok(Some(literal));
+        }
+
+        let negative = self.matches(TokenType::Minus);
+        let token = unwrap_result(self.peek())?.to_owned();
+        let allowed = if negative {
+            vec![TokenType::Integer, TokenType::Float]
+        } else {
+            vec![TokenType::Integer, TokenType::Float, TokenType::String, TokenType::Boolean, TokenType::Null]
+        };
+
+        if !allowed.contains(&token.token_type) {
+            return Err(self.invalid_token(allowed, token.token_type, token.start));
+        }
+
+        Ok(match self.primary()? {
+            Expression::LiteralExpr(literal) => Some(literal),
+            _ => error!(ParserError::InvalidStatement)
+        })
+    }
+
+    /// Parses a `-` directly followed by a number as a negative literal
+    fn negative_literal(&mut self) -> ParserResult<Option<Literal>> {
+        let Some(number) = self.lookahead().filter(|token| self.check(TokenType::Minus)
+            && matches!(token.token_type, TokenType::Integer | TokenType::Float)) else {
+            return Ok(None);
+        };
+
+        // The sign is parsed along with the digits, as the magnitude of the smallest integer doesn't fit
+        let value = format!("-{}", unwrap_result(number.value.as_ref())?.get_value());
+        let literal = match number.token_type {
+            TokenType::Integer => Literal::Integer(ast::IntegerLiteral(value.parse::<i32>()?)),
+            _ => Literal::Float(ast::FloatLiteral(value.parse::<f32>()?)),
+        };
+
+        self.advance_amt(2);
+        Ok(Some(literal))
+    }
+
     fn consume(&mut self, token: TokenType) -> ParserResult<Token> {
         if self.check(token.to_owned()) {
             return Ok(unwrap_result(self.advance())?.to_owned())
         }
 
         let found = unwrap_result(self.peek())?.to_owned();
-        error!(ParserError::InvalidToken {
-            expected: vec![token],
-            found: found.token_type,
-            pos: found.start,
-        })
+        Err(self.invalid_token(vec![token], found.token_type, found.start))
+    }
+
+    /// The error for finding `found` where one of `expected` was required. Running out of input
+    /// gets its own error pointing at the last token, as "found EndOfLine" reads awkwardly
+    fn invalid_token(&self, expected: Vec<TokenType>, found: TokenType, pos: Position) -> DynamicError {
+        // Semicolons are `EndOfLine` tokens too, but with a value as they were written out
+        let is_end = |token: &Token| token.token_type == TokenType::EndOfFile
+            || (token.token_type == TokenType::EndOfLine && token.value.is_none());
+
+        if self.tokens[self.current..].iter().all(is_end) {
+            if let Some(last) = self.tokens[..self.current].iter().rev().find(|token| !is_end(token)) {
+                return ParserError::UnexpectedEof { expected, pos: last.start.to_owned() }.into();
+            }
+        }
+
+        ParserError::InvalidToken { expected, found, pos }.into()
     }
 
     fn matches_all_in_order(&mut self, tokens: Vec<TokenType>) -> bool {
@@ -634,6 +965,11 @@ impl<'a> Parser<'a> {
         false
     }
 
+    /// Skips line ends, which comments produce, inside delimited lists such as call arguments
+    fn skip_line_ends(&mut self) {
+        while self.matches(TokenType::EndOfLine) {}
+    }
+
     fn matches(&mut self, token: TokenType) -> bool {
         if self.check(token) {
             self.advance();
@@ -695,4 +1031,303 @@ impl<'a> Parser<'a> {
         
         self.tokens.get(self.current - 1)
     }
-}
\ No newline at end of file
+}
+
+/// Left and right binding powers of the binary operators, from weakest to strongest.
+/// A higher right power makes the operator left associative, a lower one right associative
+fn binding_power(token: &TokenType) -> Option<(u8, u8)> {
+    Some(match token {
+        TokenType::Or => (1, 2),
+        TokenType::And => (3, 4),
+        TokenType::Equal | TokenType::NotEqual => (5, 6),
+        TokenType::LesserThan | TokenType::GreaterThan
+            | TokenType::LesserThanEqual | TokenType::GreaterThanEqual
+            | TokenType::In => (7, 8),
+        TokenType::Plus | TokenType::Minus => (9, 10),
+        TokenType::Multiply | TokenType::Divide | TokenType::Modulo => (11, 12),
+        TokenType::Power => (14, 13),
+        _ => return None
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{parse, statements};
+
+    use super::*;
+
+    #[test]
+    fn required_parameter_after_default_errors() {
+        let error = parse("f = (a = 1, b) { a; }").unwrap_err();
+
+        match error.downcast_ref() {
+            Some(ParserError::RequiredAfterDefault { name, .. }) => assert_eq!(name, "b"),
+            _ => panic!("expected a required after default error, got {}", error),
+        }
+    }
+
+    #[test]
+    fn rest_parameter_must_be_last() {
+        let error = parse("f = (...rest, last) { last; }").unwrap_err();
+
+        match error.downcast_ref() {
+            Some(ParserError::RestParameterNotLast { name, .. }) => assert_eq!(name, "rest"),
+            _ => panic!("expected a rest parameter error, got {}", error),
+        }
+    }
+
+    #[test]
+    fn error_position_is_found_through_the_boxed_error() {
+        let error = parse("x = 1;\ny = * 2").unwrap_err();
+        assert_eq!(crate::errors::error_position(&error).map(|pos| pos.to_tuple()), Some((2, 5)));
+    }
+
+    #[test]
+    fn reading_discard_errors() {
+        let error = parse("_ = 1;\nx = _ + 1").unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(ParserError::DiscardRead { pos }) if pos.line == 2), "{}", error);
+    }
+
+    #[test]
+    fn pretty_prints_an_indented_tree() {
+        assert_eq!(statements("1 + 2 * 3;")[0].pretty(), [
+            "ExpressionStatement",
+            "  BinaryExpression(+)",
+            "    Integer(1)",
+            "    BinaryExpression(*)",
+            "      Integer(2)",
+            "      Integer(3)",
+            "",
+        ].join("\n"));
+    }
+
+    #[test]
+    fn unclosed_paren_names_its_opener() {
+        let error = parse("x = 1;\ny = f(1,\n  2").unwrap_err();
+
+        match error.downcast_ref() {
+            Some(ParserError::UnclosedDelimiter { delimiter, pos }) => {
+                assert_eq!(*delimiter, TokenType::LeftParen);
+                assert_eq!(pos.to_tuple(), (2, 6));
+            },
+            _ => panic!("expected an unclosed delimiter error, got {}", error),
+        }
+    }
+
+    /// The pretty printed tree of the first statement, one node per line without indentation
+    fn tree(input: &str) -> Vec<String> {
+        statements(input)[0].pretty().lines().map(|line| line.trim().to_string()).collect()
+    }
+
+    #[test]
+    fn precedence_from_weakest_to_strongest() {
+        assert_eq!(tree("a || b && c == d < e + f * g ^ h;"), [
+            "ExpressionStatement",
+            "BinaryExpression(||)",
+            "Identifier(a)",
+            "BinaryExpression(&&)",
+            "Identifier(b)",
+            "BinaryExpression(==)",
+            "Identifier(c)",
+            "BinaryExpression(<)",
+            "Identifier(d)",
+            "BinaryExpression(+)",
+            "Identifier(e)",
+            "BinaryExpression(*)",
+            "Identifier(f)",
+            "BinaryExpression(^)",
+            "Identifier(g)",
+            "Identifier(h)",
+        ]);
+    }
+
+    #[test]
+    fn equality_binds_looser_than_comparison() {
+        assert_eq!(tree("a < b == c > d;")[1], "BinaryExpression(==)");
+    }
+
+    #[test]
+    fn subtraction_is_left_associative() {
+        assert_eq!(tree("a - b - c;"), [
+            "ExpressionStatement",
+            "BinaryExpression(-)",
+            "BinaryExpression(-)",
+            "Identifier(a)",
+            "Identifier(b)",
+            "Identifier(c)",
+        ]);
+    }
+
+    #[test]
+    fn power_is_right_associative() {
+        assert_eq!(tree("a ^ b ^ c;"), [
+            "ExpressionStatement",
+            "BinaryExpression(^)",
+            "Identifier(a)",
+            "BinaryExpression(^)",
+            "Identifier(b)",
+            "Identifier(c)",
+        ]);
+    }
+
+    fn statement_count(input: &str) -> usize {
+        statements(input).len()
+    }
+
+    #[test]
+    fn statements_end_at_a_newline_semicolon_or_end_of_file() {
+        assert_eq!(statement_count("a = 1\nb = 2\n"), 2);
+        assert_eq!(statement_count("a = 1; b = 2;"), 2);
+        assert_eq!(statement_count("a = 1\nb = 2"), 2);
+    }
+
+    #[test]
+    fn lines_starting_with_a_method_or_binary_operator_continue_the_statement() {
+        assert_eq!(statement_count("x = \"abc\"\n  .len()\n"), 1);
+        assert_eq!(statement_count("y = 1\n  + 2 # two\n  * 3\n"), 1);
+        assert_eq!(statement_count("x = y\n  |> f\n"), 1);
+        assert_eq!(statement_count("x = 1;\n.5\n"), 2);
+    }
+
+    #[test]
+    fn lines_starting_with_minus_or_not_are_new_statements() {
+        assert_eq!(statement_count("x = 5\n-1\n"), 2);
+        assert_eq!(statement_count("x = 5\n!y\n"), 2);
+    }
+
+    #[test]
+    fn duplicate_parameter_errors() {
+        let error = parse("f = (a, a) { a; }").unwrap_err();
+
+        match error.downcast_ref() {
+            Some(ParserError::DuplicateParameter { name, .. }) => assert_eq!(name, "a"),
+            _ => panic!("expected a duplicate parameter error, got {}", error),
+        }
+    }
+
+    #[test]
+    fn discard_parameter_may_repeat() {
+        assert!(parse("f = (_, _) { 1; }").is_ok());
+    }
+
+    #[test]
+    fn blank_lines_and_comments_leave_no_empty_statements() {
+        let ast = statements("\n\na = 1\n\n\n# comment\n\nf = () {\n\n    a;\n\n}\n\n");
+
+        let tree: String = ast.iter().map(Node::pretty).collect();
+        assert_eq!(ast.len(), 2);
+        assert!(!tree.contains("EmptyStatement"), "{}", tree);
+    }
+
+    fn nested_parens(depth: usize) -> String {
+        format!("{}1{}", "(".repeat(depth), ")".repeat(depth))
+    }
+
+    /// Runs `test` with as much stack as the main thread gets, which the default depth limit is sized for
+    fn with_main_thread_stack(test: impl FnOnce() + Send + 'static) {
+        std::thread::Builder::new()
+            .stack_size(8 * 1024 * 1024)
+            .spawn(test)
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    #[test]
+    fn deep_nesting_errors_instead_of_overflowing() {
+        with_main_thread_stack(|| {
+            let error = parse(&nested_parens(100_000)).unwrap_err();
+
+            match error.downcast_ref() {
+                Some(ParserError::NestingTooDeep { limit, .. }) => assert_eq!(*limit, ParserOptions::default().max_depth),
+                _ => panic!("expected a nesting too deep error, got {}", error),
+            }
+        });
+    }
+
+    #[test]
+    fn nesting_within_the_limit_parses() {
+        with_main_thread_stack(|| assert!(parse(&nested_parens(200)).is_ok()));
+    }
+
+    #[test]
+    fn negative_numbers_are_single_literals() {
+        assert_eq!(tree("-5;"), ["ExpressionStatement", "Integer(-5)"]);
+        assert_eq!(tree("-3.14;"), ["ExpressionStatement", "Float(-3.14)"]);
+        assert_eq!(tree("-2147483648;"), ["ExpressionStatement", "Integer(-2147483648)"]);
+    }
+
+    #[test]
+    fn minus_before_other_expressions_stays_unary() {
+        assert_eq!(tree("-x;"), ["ExpressionStatement", "UnaryExpression(-)", "Identifier(x)"]);
+        assert_eq!(tree("3 - -5;"), ["ExpressionStatement", "BinaryExpression(-)", "Integer(3)", "Integer(-5)"]);
+    }
+
+    #[test]
+    fn smallest_integer_magnitude_without_a_sign_errors() {
+        for input in ["2147483648;", "3 - 2147483648;", "-2147483649;"] {
+            let error = parse(input).unwrap_err();
+            assert!(matches!(error.downcast_ref(), Some(LexerError::NumberOutOfRange { .. })), "{}: {}", input, error);
+        }
+    }
+
+    #[test]
+    fn program_statements_can_be_walked() {
+        let program = parse("x = 1\nprintln(x)\nif x { x; }").unwrap();
+        let statements = program.as_program().expect("expected a program");
+
+        let lines: Vec<usize> = statements.iter().filter_map(|statement| statement.position().map(|pos| pos.line)).collect();
+        assert_eq!(lines, [1, 2, 3]);
+        assert!(statements[2].as_program().is_none());
+    }
+
+    #[test]
+    fn invalid_token_lists_every_expected_token() {
+        let error = parse("x = )").unwrap_err();
+
+        match error.downcast_ref() {
+            Some(ParserError::InvalidToken { expected, found, .. }) => {
+                assert_eq!(*found, TokenType::RightParen);
+                assert!(expected.len() > 1 && expected.contains(&TokenType::Integer), "{:?}", expected);
+                assert!(error.to_string().contains("expected one of"), "{}", error);
+            },
+            _ => panic!("expected an invalid token error, got {}", error),
+        }
+    }
+
+    #[test]
+    fn invalid_token_names_a_single_expected_token() {
+        let error = parse("x = y = 1").unwrap_err();
+
+        match error.downcast_ref() {
+            Some(ParserError::InvalidToken { expected, .. }) => {
+                assert_eq!(*expected, [TokenType::EndOfLine]);
+                assert!(!error.to_string().contains("one of"), "{}", error);
+            },
+            _ => panic!("expected an invalid token error, got {}", error),
+        }
+    }
+
+    #[test]
+    fn running_out_of_input_is_an_unexpected_eof() {
+        let error = parse("x = 1\ny = x +").unwrap_err();
+
+        match error.downcast_ref() {
+            Some(ParserError::UnexpectedEof { expected, pos }) => {
+                assert!(expected.contains(&TokenType::Integer), "{:?}", expected);
+                assert_eq!(pos.to_tuple(), (2, 7));
+            },
+            _ => panic!("expected an unexpected end of file error, got {}", error),
+        }
+    }
+
+    #[test]
+    fn statement_positions_carry_byte_offsets() {
+        let program = parse("x = 1\ny = 2").unwrap();
+        let offsets: Vec<usize> = program.as_program().unwrap().iter()
+            .filter_map(|statement| statement.position().map(|pos| pos.offset))
+            .collect();
+
+        assert_eq!(offsets, [0, 6]);
+    }
+}
@@ -0,0 +1,36 @@
+use crate::{errors::DynamicError, evaluator::{object::Object, Evaluator, EvaluatorOptions, EvaluatorResult}, lexer::{token::Tokens, Lexer}, parser::{ast::{Node, Program}, Parser}};
+
+/// Tokenizes `input`, panicking if it can't be lexed
+pub fn tokenize(input: &str) -> Tokens {
+    Lexer::from(input).tokenize().unwrap().to_owned()
+}
+
+/// Lexes and parses `input`
+pub fn parse(input: &str) -> Result<Program, DynamicError> {
+    let tokens = Lexer::from(input).tokenize()?.to_owned();
+    Parser::from(&tokens).parse()
+}
+
+/// The statements of `input`, panicking if it can't be lexed or parsed
+pub fn statements(input: &str) -> Vec<Node> {
+    match parse(input).unwrap() {
+        Node::Program(ast) => ast,
+        node => panic!("expected a program, got {:?}", node),
+    }
+}
+
+/// Lexes, parses and evaluates `input` with the given options
+pub fn run_with(options: EvaluatorOptions, input: &str) -> EvaluatorResult<Object> {
+    let Node::Program(ast) = parse(input)? else {
+        unreachable!("the parser always returns a program");
+    };
+
+    let mut evaluator = Evaluator::new(&ast);
+    evaluator.options = options;
+    evaluator.eval()
+}
+
+/// Lexes, parses and evaluates `input` with the default options
+pub fn run(input: &str) -> EvaluatorResult<Object> {
+    run_with(EvaluatorOptions::default(), input)
+}
@@ -7,31 +7,119 @@ pub fn unwrap_result<T>(element: Option<T>) -> Result<T, DynamicError> {
     }
 }
 
-#[cfg(not(target_os = "windows"))]
 pub mod colors {
-    pub const RED: &str = "\x1b[31m";
-    pub const GREEN: &str = "\x1b[32m";
-    pub const YELLOW: &str = "\x1b[33m";
-    pub const BLUE: &str = "\x1b[34m";
-    pub const MAGENTA: &str = "\x1b[35m";
-    pub const CYAN: &str = "\x1b[36m";
-
-    pub const RESET: &str = "\x1b[0m";
-    pub const BOLD: &str = "\x1b[1m";
-    pub const UNDERLINE: &str = "\x1b[4m";
-}
+    use std::io::{stdout, IsTerminal};
 
-// TODO: SUpport this somehow
-#[cfg(target_os = "windows")]
-pub mod colors {
-    pub const RED: &str = "";
-    pub const GREEN: &str = "";
-    pub const YELLOW: &str = "";
-    pub const BLUE: &str = "";
-    pub const MAGENTA: &str = "";
-    pub const CYAN: &str = "";
-
-    pub const RESET: &str = "";
-    pub const BOLD: &str = "";
-    pub const UNDERLINE: &str = "";
+    pub use self::codes::*;
+
+    #[cfg(not(target_os = "windows"))]
+    mod codes {
+        pub const RED: &str = "\x1b[31m";
+        pub const GREEN: &str = "\x1b[32m";
+        pub const YELLOW: &str = "\x1b[33m";
+        pub const BLUE: &str = "\x1b[34m";
+        pub const MAGENTA: &str = "\x1b[35m";
+        pub const CYAN: &str = "\x1b[36m";
+
+        pub const RESET: &str = "\x1b[0m";
+        pub const BOLD: &str = "\x1b[1m";
+        pub const UNDERLINE: &str = "\x1b[4m";
+    }
+
+    // TODO: SUpport this somehow
+    #[cfg(target_os = "windows")]
+    mod codes {
+        pub const RED: &str = "";
+        pub const GREEN: &str = "";
+        pub const YELLOW: &str = "";
+        pub const BLUE: &str = "";
+        pub const MAGENTA: &str = "";
+        pub const CYAN: &str = "";
+
+        pub const RESET: &str = "";
+        pub const BOLD: &str = "";
+        pub const UNDERLINE: &str = "";
+    }
+
+    /// The codes used when printing, so coloring can be chosen at runtime
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct Palette {
+        pub red: &'static str,
+        pub green: &'static str,
+        pub yellow: &'static str,
+        pub blue: &'static str,
+        pub magenta: &'static str,
+        pub cyan: &'static str,
+
+        pub reset: &'static str,
+        pub bold: &'static str,
+        pub underline: &'static str,
+    }
+
+    impl Palette {
+        pub const fn ansi() -> Self {
+            Self {
+                red: RED,
+                green: GREEN,
+                yellow: YELLOW,
+                blue: BLUE,
+                magenta: MAGENTA,
+                cyan: CYAN,
+                reset: RESET,
+                bold: BOLD,
+                underline: UNDERLINE,
+            }
+        }
+
+        pub const fn none() -> Self {
+            Self {
+                red: "",
+                green: "",
+                yellow: "",
+                blue: "",
+                magenta: "",
+                cyan: "",
+                reset: "",
+                bold: "",
+                underline: "",
+            }
+        }
+    }
+
+    impl Default for Palette {
+        fn default() -> Self {
+            Self::ansi()
+        }
+    }
+
+    /// When to color output, as set by `--color=auto|always|never`
+    #[derive(Debug, Clone, Copy, Default, PartialEq)]
+    pub enum ColorChoice {
+        /// Color only when stdout is a terminal and `NO_COLOR` isn't set
+        #[default]
+        Auto,
+        Always,
+        Never,
+    }
+
+    impl ColorChoice {
+        pub fn from(value: &str) -> Option<Self> {
+            match value {
+                "auto" => Some(Self::Auto),
+                "always" => Some(Self::Always),
+                "never" => Some(Self::Never),
+                _ => None
+            }
+        }
+
+        pub fn palette(&self) -> Palette {
+            let enabled = match self {
+                ColorChoice::Always => true,
+                ColorChoice::Never => false,
+                ColorChoice::Auto => stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty()),
+            };
+
+            if enabled { Palette::ansi() } else { Palette::none() }
+        }
+    }
 }
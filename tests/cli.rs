@@ -0,0 +1,33 @@
+use std::{fs, process::{Command, Output}};
+
+/// Runs the interpreter binary on a script written to a temporary file
+fn run_script(name: &str, script: &str) -> Output {
+    let path = std::env::temp_dir().join(format!("yaipl-cli-{}-{}", std::process::id(), name));
+    fs::write(&path, script).expect("Could not write temp file");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_another-interpreted-language"))
+        .arg("--color=never")
+        .arg(&path)
+        .output()
+        .expect("Could not run the interpreter");
+
+    let _ = fs::remove_file(&path);
+    output
+}
+
+#[test]
+fn script_without_print_writes_nothing_to_stdout() {
+    let output = run_script("quiet.yaipl", "x = 1 + 2\nassert_eq(x * 3, 9)\n");
+
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+}
+
+#[test]
+fn printed_values_and_the_result_reach_stdout() {
+    // The command line shows the value of the last statement, interpret itself prints nothing
+    let output = run_script("printing.yaipl", "x = 1 + 2\nprintln(x)\nx * 3\n");
+
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n9\n");
+}